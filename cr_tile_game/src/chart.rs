@@ -0,0 +1,29 @@
+//! chart is a source file containing the loading of charts from the charts directory
+#![warn(missing_docs)]
+
+use crate::user_files::{list_json_files, user_dir};
+use cr_tile_game_common::chart::Chart;
+use std::fs;
+use std::path::PathBuf;
+
+/// Returns the directory charts are loaded from, next to the settings of the game
+pub fn chart_dir() -> Option<PathBuf> {
+    user_dir("charts")
+}
+
+/// Loads every valid chart in the chart directory, charts that can not be loaded are skipped with an error printed
+pub fn list_charts() -> Vec<(PathBuf, Chart)> {
+    list_json_files(chart_dir())
+        .into_iter()
+        .filter_map(|path| {
+            let json = fs::read_to_string(&path).ok()?;
+            match Chart::from_json(&json) {
+                Ok(chart) => Some((path, chart)),
+                Err(err) => {
+                    println!("{}: {:?}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
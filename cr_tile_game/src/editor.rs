@@ -0,0 +1,460 @@
+//! editor is a source file containing the chart editor, where charts are made by placing tiles on a beat grid over a song
+#![warn(missing_docs)]
+
+use crate::hud;
+use crate::music::{chart_audio_path, WavSong};
+use crate::tile::{DrawTile, TILE_WIDTH};
+use cr_tile_game_common::chart::{Chart, ChartNote};
+use cr_tile_game_common::tile::{Tile, TileKind, MIDDLE_BAR, TILE_HEIGHT};
+use macroquad::audio::{play_sound, stop_sound, PlaySoundParams, Sound};
+use macroquad::hash;
+use macroquad::prelude::*;
+use macroquad::ui::{root_ui, widgets};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// The number of pixels the timeline scrolls by for every second of the chart
+const PIXELS_PER_SECOND: f32 = 240.0;
+/// The number of grid lines each beat can be split into
+const GRID_DIVISIONS: [u32; 4] = [1, 2, 3, 4];
+/// The number of beats in a bar, used to jump through the chart a bar at a time
+const BEATS_PER_BAR: f32 = 4.0;
+/// The tempo new charts start with
+const DEFAULT_BPM: f32 = 120.0;
+/// The speed tiles are placed with when nothing else has been picked
+const DEFAULT_NOTE_SPEED: f32 = 4.0;
+/// The types of tile that can be placed, along with the name shown for each
+const PLACEABLE_KINDS: [(&str, TileKind); 4] = [
+    ("Normal", TileKind::Normal),
+    ("Chord", TileKind::Chord { slots: 0 }),
+    ("Bomb", TileKind::Bomb),
+    ("Bonus", TileKind::Bonus),
+];
+/// The width of the panel to the right of the timeline
+pub const PANEL_WIDTH: f32 = 300.0;
+
+/// Something the editor needs the rest of the game to do, because it is async or leaves the editor
+pub enum EditorAction {
+    /// Start or stop playing the song from the cursor
+    TogglePreview,
+    /// Test play the chart starting at the cursor
+    TestPlay,
+    /// Leave the editor
+    Back,
+}
+
+/// A chart being edited, along with the state of the editor
+pub struct ChartEditor {
+    /// The file the chart is saved to
+    pub path: PathBuf,
+    /// The chart being edited
+    pub chart: Chart,
+    /// The text in the audio path box
+    audio_input: String,
+    /// The text in the bpm box
+    bpm_input: String,
+    /// The index in `GRID_DIVISIONS` of the current grid
+    division_index: usize,
+    /// The time in seconds of the chart that is on the hit bar
+    pub cursor: f32,
+    /// The speed new tiles are placed with
+    note_speed: f32,
+    /// The index in `PLACEABLE_KINDS` of the type of tile that is placed
+    kind_index: usize,
+    /// The song of the chart, if it is a wav file that could be loaded
+    pub song: Option<Rc<WavSong>>,
+    /// The song being previewed, along with when and where in the chart it started
+    preview: Option<(Sound, Instant, f32)>,
+    /// The slot and snapped time a left click started on, a hold tile is placed if it is let go of at a later time
+    drag_start: Option<(u8, f32)>,
+    /// A message about the last thing the editor did
+    pub status: String,
+}
+
+impl ChartEditor {
+    /// Opens the chart for editing, it is saved to the given path
+    pub fn new(path: PathBuf, chart: Chart) -> Self {
+        let mut editor = Self {
+            audio_input: chart.audio.clone().unwrap_or_default(),
+            bpm_input: chart.bpm.unwrap_or(DEFAULT_BPM).to_string(),
+            path,
+            chart,
+            division_index: GRID_DIVISIONS.len() - 1,
+            cursor: 0.0,
+            note_speed: DEFAULT_NOTE_SPEED,
+            kind_index: 0,
+            song: None,
+            preview: None,
+            drag_start: None,
+            status: String::new(),
+        };
+        editor.load_song();
+        editor
+    }
+
+    /// Creates an empty chart, saved in the chart directory
+    pub fn new_chart(dir: PathBuf, author: String, slot_count: u8) -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let chart = Chart {
+            title: "New chart".to_string(),
+            author,
+            slot_count,
+            audio: None,
+            bpm: Some(DEFAULT_BPM),
+            notes: vec![],
+        };
+        Self::new(dir.join(format!("chart_{}.json", secs)), chart)
+    }
+
+    /// Returns the width of the screen the editor needs
+    pub fn screen_width(&self) -> f32 {
+        self.chart.slot_count as f32 * TILE_WIDTH + PANEL_WIDTH
+    }
+
+    /// Loads the song of the chart so it can be previewed, only wav songs can be previewed
+    fn load_song(&mut self) {
+        self.song = None;
+        let Some(path) = chart_audio_path(&self.path, &self.chart) else {
+            return;
+        };
+        match WavSong::load(&path) {
+            Ok(song) => {
+                self.song = Some(Rc::new(song));
+            }
+            Err(err) => {
+                self.status = format!("Song can not be previewed: {}", err);
+            }
+        }
+    }
+
+    /// Returns the tempo the grid is drawn with
+    fn bpm(&self) -> f32 {
+        self.chart.bpm.unwrap_or(DEFAULT_BPM).max(1.0)
+    }
+
+    /// Returns the length in seconds of a single step of the grid
+    fn grid_step(&self) -> f32 {
+        60.0 / self.bpm() / GRID_DIVISIONS[self.division_index] as f32
+    }
+
+    /// Returns the time closest to the given time that is on the grid
+    fn snap(&self, time: f32) -> f32 {
+        let step = self.grid_step();
+        ((time / step).round() * step).max(0.0)
+    }
+
+    /// Returns the time of the latest point the cursor can be moved to
+    fn end_time(&self) -> f32 {
+        let song_length = self.song.as_ref().map_or(0.0, |song| song.length());
+        song_length.max(self.chart.length() + BEATS_PER_BAR * 60.0 / self.bpm())
+    }
+
+    /// Moves the cursor by the given number of seconds, and snaps it to the grid
+    fn move_cursor(&mut self, seconds: f32) {
+        self.cursor = self.snap(self.cursor + seconds).min(self.end_time());
+    }
+
+    /// Returns the time of the chart at the given y coordinate of the screen
+    fn time_at(&self, y: f32) -> f32 {
+        self.cursor + (MIDDLE_BAR - y) / PIXELS_PER_SECOND
+    }
+
+    /// Returns the y coordinate of the screen the top of a tile at the given time is drawn at
+    fn y_at(&self, time: f32) -> f32 {
+        MIDDLE_BAR - (time - self.cursor) * PIXELS_PER_SECOND - (TILE_HEIGHT / 2.0)
+    }
+
+    /// Places a tile of the given type in the slot at the given time, unless one is already there
+    fn place_note(&mut self, slot: u8, time: f32, kind: TileKind) {
+        let exists = self
+            .chart
+            .notes
+            .iter()
+            .any(|note| note.slot == slot && (note.time - time).abs() < 0.001);
+        if !exists {
+            self.chart.notes.push(ChartNote {
+                time,
+                slot,
+                kind,
+                speed: self.note_speed,
+            });
+        }
+    }
+
+    /// Adds the slot to the chord at the given time, or starts a new chord there if there is none.
+    /// A chord needs a second slot added before the chart can be saved
+    fn place_chord(&mut self, slot: u8, time: f32) {
+        let chord = self.chart.notes.iter_mut().find(|note| {
+            matches!(note.kind, TileKind::Chord { .. }) && (note.time - time).abs() < 0.001
+        });
+        match chord {
+            Some(ChartNote {
+                kind: TileKind::Chord { slots },
+                ..
+            }) => {
+                *slots |= 1 << slot;
+            }
+            _ => {
+                self.chart.notes.push(ChartNote {
+                    time,
+                    slot,
+                    kind: TileKind::Chord { slots: 1 << slot },
+                    speed: self.note_speed,
+                });
+            }
+        }
+    }
+
+    /// Deletes the tile drawn at the given y coordinate in the slot, if there is one, the tail of a hold tile counts as part of it
+    fn delete_note(&mut self, slot: u8, y: f32) {
+        let cursor = self.cursor;
+        if let Some(index) = self.chart.notes.iter().position(|note| {
+            let top = MIDDLE_BAR - (note.time - cursor) * PIXELS_PER_SECOND - (TILE_HEIGHT / 2.0);
+            let tail = note.duration() * PIXELS_PER_SECOND;
+            note.covers(slot) && y >= top - tail && y <= top + TILE_HEIGHT
+        }) {
+            self.chart.notes.remove(index);
+        }
+    }
+
+    /// Returns true while the song is being previewed
+    pub fn is_previewing(&self) -> bool {
+        self.preview.is_some()
+    }
+
+    /// Starts previewing the song from the cursor
+    pub fn start_preview(&mut self, sound: Sound, volume: f32) {
+        play_sound(
+            &sound,
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        );
+        self.preview = Some((sound, Instant::now(), self.cursor));
+    }
+
+    /// Stops previewing the song, leaving the cursor where the song got to
+    pub fn stop_preview(&mut self) {
+        if let Some((sound, _, _)) = self.preview.take() {
+            stop_sound(&sound);
+        }
+        self.cursor = self.snap(self.cursor);
+    }
+
+    /// Returns the chart with the values of the text boxes applied
+    fn apply_inputs(&mut self) {
+        let audio = self.audio_input.trim();
+        let audio = (!audio.is_empty()).then(|| audio.to_string());
+        if audio != self.chart.audio {
+            self.chart.audio = audio;
+            self.load_song();
+        }
+        if let Ok(bpm) = self.bpm_input.trim().parse::<f32>() {
+            if bpm > 0.0 {
+                self.chart.bpm = Some(bpm);
+            }
+        }
+    }
+
+    /// Saves the chart to its file
+    fn save(&mut self) {
+        self.apply_inputs();
+        self.chart
+            .notes
+            .sort_by(|a, b| a.time.total_cmp(&b.time).then(a.slot.cmp(&b.slot)));
+        if let Err(err) = self.chart.validate() {
+            self.status = format!("Chart is not valid: {:?}", err);
+            return;
+        }
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, self.chart.to_json()));
+        self.status = match result {
+            Ok(_) => format!("Saved to {}", self.path.display()),
+            Err(err) => format!("Failed to save: {}", err),
+        };
+    }
+
+    /// Takes mouse and keyboard input for the timeline
+    pub fn handle_input(&mut self) {
+        if let Some((_, started, start_cursor)) = &self.preview {
+            self.cursor = start_cursor + started.elapsed().as_secs_f32();
+            if self.cursor >= self.end_time() {
+                self.stop_preview();
+            }
+            return;
+        }
+
+        let step = self.grid_step();
+        let bar = BEATS_PER_BAR * 60.0 / self.bpm();
+        let (_, wheel) = mouse_wheel();
+        if wheel > 0.0 || is_key_pressed(KeyCode::Up) {
+            self.move_cursor(step);
+        }
+        if wheel < 0.0 || is_key_pressed(KeyCode::Down) {
+            self.move_cursor(-step);
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            self.move_cursor(bar);
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            self.move_cursor(-bar);
+        }
+
+        let (x, y) = mouse_position();
+        // a drag places its tile wherever the mouse is let go of, so it is handled before the mouse is checked to be on the timeline
+        if is_mouse_button_released(MouseButton::Left) {
+            if let Some((slot, start)) = self.drag_start.take() {
+                let end = self.snap(self.time_at(y));
+                match PLACEABLE_KINDS[self.kind_index].1 {
+                    TileKind::Normal if end > start => {
+                        let kind = TileKind::Hold {
+                            duration: end - start,
+                        };
+                        self.place_note(slot, start, kind);
+                    }
+                    TileKind::Chord { .. } => self.place_chord(slot, start),
+                    kind => self.place_note(slot, start, kind),
+                }
+            }
+        }
+
+        let timeline_width = self.chart.slot_count as f32 * TILE_WIDTH;
+        if x < 0.0 || x >= timeline_width || y < 0.0 {
+            return;
+        }
+        let slot = (x / TILE_WIDTH) as u8;
+        if is_mouse_button_pressed(MouseButton::Left) {
+            self.drag_start = Some((slot, self.snap(self.time_at(y))));
+        }
+        if is_mouse_button_pressed(MouseButton::Right) {
+            self.delete_note(slot, y);
+        }
+    }
+
+    /// Draws the grid, the hit bar and every tile on the timeline
+    pub fn draw(&self) {
+        let timeline_width = self.chart.slot_count as f32 * TILE_WIDTH;
+
+        // grid lines, from the bottom of the screen to the top
+        let step = self.grid_step();
+        let divisions = GRID_DIVISIONS[self.division_index];
+        let first = (self.time_at(screen_height()) / step).floor().max(0.0) as u32;
+        let last = (self.time_at(0.0) / step).ceil() as u32;
+        for line in first..=last {
+            let y = self.y_at(line as f32 * step) + (TILE_HEIGHT / 2.0);
+            let on_beat = line % divisions == 0;
+            draw_line(
+                0.0,
+                y,
+                timeline_width,
+                y,
+                if on_beat { 2.0 } else { 1.0 },
+                if on_beat { BLACK } else { DARKGRAY },
+            );
+        }
+
+        hud::draw_hit_bar(self.chart.slot_count);
+
+        for (index, note) in self.chart.notes.iter().enumerate() {
+            let mut tile = Tile::new(index as u32, note.slot, note.speed).with_kind(note.kind);
+            tile.distance = self.y_at(note.time);
+            // the timeline scrolls at the same rate for every tile, whatever speed it falls at in the game
+            tile.length = note.duration() * PIXELS_PER_SECOND;
+            if tile.distance - tile.length > -TILE_HEIGHT && tile.distance < screen_height() {
+                tile.draw();
+            }
+        }
+    }
+
+    /// Draws the panel to the right of the timeline, and returns what the player asked to do
+    pub fn draw_panel(&mut self) -> Option<EditorAction> {
+        let mut action = None;
+        let mut save = false;
+        let mut title = self.chart.title.clone();
+        let mut author = self.chart.author.clone();
+        let timeline_width = self.chart.slot_count as f32 * TILE_WIDTH;
+
+        widgets::Window::new(
+            hash!(),
+            vec2(timeline_width, 0.0),
+            vec2(PANEL_WIDTH, screen_height()),
+        )
+        .label("Chart editor")
+        .movable(false)
+        .titlebar(true)
+        .ui(&mut root_ui(), |ui| {
+            ui.input_text(hash!(), "Title", &mut title);
+            ui.input_text(hash!(), "Author", &mut author);
+            ui.input_text(hash!(), "Song", &mut self.audio_input);
+            ui.input_text(hash!(), "BPM", &mut self.bpm_input);
+            if ui.button(None, "Apply song and BPM") {
+                self.apply_inputs();
+            }
+            ui.separator();
+            ui.label(None, &format!("Cursor: {:.3}s", self.cursor));
+            if ui.button(
+                None,
+                format!("Grid: 1/{} beat", GRID_DIVISIONS[self.division_index]),
+            ) {
+                self.division_index = (self.division_index + 1) % GRID_DIVISIONS.len();
+            }
+            if ui.button(
+                None,
+                format!("Tile: {}", PLACEABLE_KINDS[self.kind_index].0),
+            ) {
+                self.kind_index = (self.kind_index + 1) % PLACEABLE_KINDS.len();
+            }
+            ui.label(None, &format!("Tile speed: {}", self.note_speed));
+            if ui.button(None, "Speed -") {
+                self.note_speed = (self.note_speed - 1.0).max(1.0);
+            }
+            ui.same_line(0.0);
+            if ui.button(None, "Speed +") {
+                self.note_speed += 1.0;
+            }
+            ui.separator();
+            if ui.button(
+                None,
+                if self.is_previewing() {
+                    "Stop song"
+                } else {
+                    "Play song"
+                },
+            ) {
+                action = Some(EditorAction::TogglePreview);
+            }
+            if ui.button(None, "Test from cursor") {
+                action = Some(EditorAction::TestPlay);
+            }
+            if ui.button(None, "Save") {
+                save = true;
+            }
+            if ui.button(None, "Back") {
+                action = Some(EditorAction::Back);
+            }
+            ui.separator();
+            ui.label(None, "Left click: place, right click: delete");
+            ui.label(None, "Left drag up: place a hold");
+            ui.label(None, "Chords: click each slot of the chord");
+            ui.label(None, "Scroll, Up/Down: move a grid step");
+            ui.label(None, "Page Up/Page Down: move a bar");
+            ui.label(None, &self.status);
+        });
+
+        self.chart.title = title;
+        self.chart.author = author;
+        if save {
+            self.save();
+        }
+        action
+    }
+}
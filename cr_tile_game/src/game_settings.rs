@@ -4,6 +4,49 @@
 use cr_program_settings::{load_settings, save_settings};
 use serde::{Deserialize, Serialize};
 
+/// The smallest audio offset that can be set, in milliseconds
+pub const MIN_AUDIO_OFFSET: f32 = -500.0;
+/// The largest audio offset that can be set, in milliseconds
+pub const MAX_AUDIO_OFFSET: f32 = 500.0;
+/// The slowest tile speed that can be practiced at, in pixels per tick
+pub const MIN_PRACTICE_SPEED: f32 = 1.0;
+/// The fastest tile speed that can be practiced at, in pixels per tick
+pub const MAX_PRACTICE_SPEED: f32 = 10.0;
+/// The shortest time between tiles that can be practiced with, in seconds
+pub const MIN_PRACTICE_INTERVAL: f32 = 0.25;
+/// The longest time between tiles that can be practiced with, in seconds
+pub const MAX_PRACTICE_INTERVAL: f32 = 3.0;
+/// The slowest a practice run can be slowed down to, as a multiplier of normal speed
+pub const MIN_SLOW_MOTION: f32 = 0.25;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+/// A named game service the user has saved, along with the credentials used on it
+pub struct SavedServer {
+    /// The name shown in the server selector
+    pub name: String,
+    /// The ip address of the service
+    pub ip_address: String,
+    /// The username the user logs in with on this service
+    pub username: String,
+    /// The key the user logs in with on this service
+    pub key: String,
+    /// Favorite servers are listed before every other server
+    pub favorite: bool,
+}
+
+impl Default for SavedServer {
+    fn default() -> Self {
+        Self {
+            name: "".to_string(),
+            ip_address: "localhost:8114".to_string(),
+            username: "".to_string(),
+            key: "".to_string(),
+            favorite: false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(default)]
 /// Struct representing items to save and load on program startup
@@ -14,10 +57,22 @@ pub struct GameSettings {
     pub username: String,
     /// The key the user uses to log in with
     pub key: String,
-    /// The volume that the tick noises play at
+    /// The volume that the tick noises and the songs of charts play at
     pub volume: f32,
     /// The number of slots the game uses for gameplay
     pub slot_count: u8,
+    /// The number of milliseconds sound takes to be heard after it is played, tiles of charts are delayed by this much to line up with their song
+    pub audio_offset: f32,
+    /// The name of the server that was last selected, restored on startup
+    pub last_server: String,
+    /// Every server the user has saved, favorites first
+    pub servers: Vec<SavedServer>,
+    /// The speed every tile falls at in practice runs, in pixels per tick
+    pub practice_speed: f32,
+    /// The seconds between tiles spawning in practice runs
+    pub practice_interval: f32,
+    /// How fast practice runs play compared to normal speed, 1.0 is normal speed
+    pub slow_motion: f32,
 }
 
 impl Default for GameSettings {
@@ -28,6 +83,12 @@ impl Default for GameSettings {
             key: "".to_string(),
             volume: 1.0,
             slot_count: 3,
+            audio_offset: 0.0,
+            last_server: "".to_string(),
+            servers: vec![],
+            practice_speed: 2.0,
+            practice_interval: 1.5,
+            slow_motion: 1.0,
         }
     }
 }
@@ -44,7 +105,80 @@ impl GameSettings {
         // restrict specific values so the game cant be broken that easily :P
         settings.volume = settings.volume.clamp(0.0, 1.0);
         settings.slot_count = settings.slot_count.clamp(1, 5);
+        settings.audio_offset = settings
+            .audio_offset
+            .clamp(MIN_AUDIO_OFFSET, MAX_AUDIO_OFFSET);
+        settings.practice_speed = settings
+            .practice_speed
+            .clamp(MIN_PRACTICE_SPEED, MAX_PRACTICE_SPEED);
+        settings.practice_interval = settings
+            .practice_interval
+            .clamp(MIN_PRACTICE_INTERVAL, MAX_PRACTICE_INTERVAL);
+        settings.slow_motion = settings.slow_motion.clamp(MIN_SLOW_MOTION, 1.0);
+        settings.sort_servers();
+
+        // restore the credentials of the last used server, if it still exists
+        if let Some(server) = settings.get_server(&settings.last_server).cloned() {
+            settings.apply_server(&server);
+        }
 
         settings
     }
+
+    /// Returns the saved server with the given name
+    pub fn get_server(&self, name: &str) -> Option<&SavedServer> {
+        self.servers.iter().find(|server| server.name == name)
+    }
+
+    /// Returns the index of the saved server with the given name
+    pub fn get_server_index(&self, name: &str) -> Option<usize> {
+        self.servers.iter().position(|server| server.name == name)
+    }
+
+    /// Copies the address and credentials of a saved server into the current settings, and remembers it as the last used server
+    pub fn apply_server(&mut self, server: &SavedServer) {
+        self.ip_address = server.ip_address.clone();
+        self.username = server.username.clone();
+        self.key = server.key.clone();
+        self.last_server = server.name.clone();
+    }
+
+    /// Saves the current address and credentials under the given name, replacing any server that already has that name
+    pub fn save_server(&mut self, name: &str) {
+        let server = SavedServer {
+            name: name.to_string(),
+            ip_address: self.ip_address.clone(),
+            username: self.username.clone(),
+            key: self.key.clone(),
+            favorite: self.get_server(name).is_some_and(|server| server.favorite),
+        };
+
+        match self.get_server_index(name) {
+            None => self.servers.push(server),
+            Some(index) => self.servers[index] = server,
+        }
+        self.last_server = name.to_string();
+        self.sort_servers();
+    }
+
+    /// Removes the saved server with the given name
+    pub fn delete_server(&mut self, name: &str) {
+        self.servers.retain(|server| server.name != name);
+        if self.last_server == name {
+            self.last_server = "".to_string();
+        }
+    }
+
+    /// Flips if the saved server with the given name is a favorite
+    pub fn toggle_favorite(&mut self, name: &str) {
+        if let Some(index) = self.get_server_index(name) {
+            self.servers[index].favorite = !self.servers[index].favorite;
+        }
+        self.sort_servers();
+    }
+
+    /// Moves favorite servers to the front of the list, keeping the order they were saved in otherwise
+    fn sort_servers(&mut self) {
+        self.servers.sort_by_key(|server| !server.favorite);
+    }
 }
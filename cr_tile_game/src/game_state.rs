@@ -1,18 +1,39 @@
 use crate::background_elements::BackgroundTileList;
-use crate::game_state::ClientError::{DeserializationError, PacketError, SocketReadError};
+use crate::chart::list_charts;
+use crate::editor::ChartEditor;
+use crate::game_state::ClientError::{PacketError, SocketReadError, SocketWriteError};
 use crate::game_state::GameState::Playing;
-use crate::tile::Tile;
-use crate::{ANTI_TICK_SOUND, SLOT_COUNT};
+use crate::music::ChartMusic;
+use crate::pause::PauseState;
+use crate::preset::{list_presets, mode_preset};
+use crate::replay::{list_replays, save_replay, ReplayPlayer};
+use crate::spectate::SpectateState;
+use crate::tile::DrawTile;
+use crate::versus::{VersusState, VERSUS_UPDATE_INTERVAL};
+use crate::{ANTI_TICK_SOUND, SLOT_COUNT, TICK_SOUND};
+use cr_tile_game_common::chart::Chart;
+use cr_tile_game_common::daily::DailyChallenge;
+pub use cr_tile_game_common::daily::Difficulty;
+use cr_tile_game_common::judgement::Judgement;
 use cr_tile_game_common::leader_board_stat::LeaderBoardList;
-use cr_tile_game_common::packet::{ClientPacket, GameDataPacket, LoginInfo, ServerPacket};
+use cr_tile_game_common::packet::{
+    drain_packets, CategoryScorePacket, ClientPacket, DailyAttemptPacket, DailyScorePacket,
+    GameDataPacket, LoginInfo, ServerPacket, VersusJoin, VersusStatus,
+};
+use cr_tile_game_common::preset::DifficultyPreset;
+use cr_tile_game_common::replay::Replay;
+use cr_tile_game_common::simulation::{SimEvent, Simulation};
+use cr_tile_game_common::spectate::{LiveEvent, LiveGameInfo};
+use cr_tile_game_common::timestep::FixedTimestep;
 use macroquad::audio::play_sound_once;
 use macroquad::prelude::request_new_screen_size;
-use rand::prelude::SliceRandom;
 use std::cell::Cell;
+use std::collections::VecDeque;
 use std::io::{Read, Write};
 use std::net::TcpStream;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
-use std::time::SystemTime;
+use std::time::Instant;
 
 #[derive(PartialEq, Eq, Clone)]
 /// The state representing the player is doing.
@@ -21,12 +42,24 @@ pub enum GameState {
     Playing(Difficulty),
     ScoreScreen,
     Leaderboards,
-}
-
-#[derive(Clone, Eq, PartialEq)]
-pub enum Difficulty {
-    Normal,
-    Hard,
+    /// Waiting for the server to find an opponent for a versus match
+    VersusQueue,
+    /// Picking a live game to spectate
+    SpectateList,
+    /// Watching the game of another player
+    Spectating,
+    /// Picking a saved replay to watch
+    ReplayList,
+    /// Watching a saved replay
+    WatchingReplay,
+    /// Picking a chart to play
+    ChartList,
+    /// Picking a difficulty preset to play
+    PresetList,
+    /// Picking the tile speed, spawn interval and slow motion of a practice run
+    PracticeSetup,
+    /// Editing a chart
+    Editor,
 }
 
 /// A struct representing the entire game state.
@@ -34,58 +67,112 @@ pub struct TileGameState {
     /// The state representing what should be going on e.g. at main menu, or playing in normal mode, or seeing the score screen.
     pub state: GameState,
 
-    /// A vector containing all the tiles that are in the game
-    pub tiles: Vec<Tile>,
-    /// An array that is the size of the number of slots in the game, which has contents that represent the time since the last slot has been pressed
-    pub slot_press_time: Vec<SystemTime>,
+    /// The rules of the run being played, with no rendering or sound
+    pub sim: Simulation,
 
-    /// The time since the last tile was spawned
-    pub time_since_tile: SystemTime,
+    /// The clock that decides how many ticks of the simulation to run each frame
+    pub timestep: FixedTimestep,
 
-    /// The number of seconds delayed between spawning tiles
-    pub tile_spawn_time: f32,
+    pub client: Cell<Option<TcpStream>>,
 
-    /// The number of tiles the player has hit
-    pub tile_hit_count: i32,
+    pub login_info: LoginInfo,
 
-    /// The number of lives the player has
-    pub lives: i32,
+    pub leader_boards: Option<LeaderBoardList>,
 
-    /// The number of times the player has hit a slot key
-    pub slot_clicks: i32,
+    pub ip_address: String,
 
-    /// The time that the game started at
-    pub game_start_time: SystemTime,
+    /// The versus match the player is in, if they are playing against someone
+    pub versus: Option<VersusState>,
 
-    /// The time that the game ended at
-    pub game_end_time: SystemTime,
+    /// Bytes read from the server that have not formed a full packet yet
+    pub packet_buf: Vec<u8>,
 
-    pub client: Cell<Option<TcpStream>>,
+    /// Packets read from the server that arrived along with the reply to a request, handed out before anything read later
+    pub pending_packets: VecDeque<ServerPacket>,
 
-    pub login_info: LoginInfo,
+    /// True if the game is being streamed to the server for others to spectate
+    pub live: bool,
 
-    pub leader_boards: Option<LeaderBoardList>,
+    /// Events that have happened since they were last sent to spectators
+    pub live_events: Vec<LiveEvent>,
 
-    pub ip_address: String,
+    /// The games that can be spectated, as of the last time the server was asked
+    pub live_games: Vec<LiveGameInfo>,
+
+    /// The game the player is watching, if they are spectating
+    pub spectating: Option<SpectateState>,
+
+    /// The daily challenge being played, if the player is playing it
+    pub daily: Option<DailyChallenge>,
+
+    /// Once the daily challenge score is submitted, true if it was the ranked attempt for the day
+    pub daily_ranked: Option<bool>,
+
+    /// The path the replay of the last run was saved to, once it has been saved
+    pub replay_path: Option<PathBuf>,
+
+    /// The saved replays, as of the last time the replay directory was read
+    pub replays: Vec<(PathBuf, Replay)>,
+
+    /// The replay being watched, if the player is watching one
+    pub replay: Option<ReplayPlayer>,
+
+    /// The charts that can be played, as of the last time the chart directory was read
+    pub charts: Vec<(PathBuf, Chart)>,
+
+    /// The difficulty presets that can be played, as of the last time the preset directory was read
+    pub presets: Vec<(PathBuf, DifficultyPreset)>,
+
+    /// The song of the chart being played, the simulation is kept in time with it while it plays
+    pub music: Option<ChartMusic>,
+
+    /// The chart being edited, kept while the chart is test played
+    pub editor: Option<ChartEditor>,
+
+    /// The last judgement the player was given, and the tick it was given on
+    pub judgement: Option<(Judgement, u64)>,
+
+    /// How fast the run plays compared to normal speed, only slowed down in practice runs
+    pub slow_motion: f32,
+
+    /// Ticks that have been earned by the clock while slowed down but not simulated yet, not counting the part of a tick the clock is into.
+    /// Negative when the part of a tick the clock is into has already been simulated
+    pub tick_budget: f32,
+
+    /// The pause the run is in, if the player has paused it
+    pub pause: Option<PauseState>,
 }
 
 impl Default for TileGameState {
     fn default() -> Self {
         Self {
             state: GameState::MainMenu(BackgroundTileList::new()),
-            tiles: vec![],
-            slot_press_time: TileGameState::new_slot_press_time(),
-            time_since_tile: SystemTime::now(),
-            tile_spawn_time: 1.5,
-            tile_hit_count: 0,
-            lives: 10,
-            slot_clicks: 0,
-            game_start_time: SystemTime::UNIX_EPOCH,
-            game_end_time: SystemTime::now(),
+            sim: Simulation::default(),
+            timestep: FixedTimestep::new(),
             client: Cell::new(None),
             login_info: LoginInfo::default(),
             leader_boards: None,
             ip_address: "localhost:8114".to_string(),
+            versus: None,
+            packet_buf: vec![],
+            pending_packets: VecDeque::new(),
+            live: false,
+            live_events: vec![],
+            live_games: vec![],
+            spectating: None,
+            daily: None,
+            daily_ranked: None,
+            replay_path: None,
+            replays: vec![],
+            replay: None,
+            charts: vec![],
+            presets: vec![],
+            music: None,
+            editor: None,
+            judgement: None,
+            slow_motion: 1.0,
+            tick_budget: 0.0,
+            pause: None,
         }
     }
 }
@@ -98,16 +185,16 @@ pub enum ClientError {
     // NoClientConnected,
     /// Socket was unable to read bytes
     SocketReadError,
-    /// Socket read bytes, but failed to deserialize them
-    DeserializationError,
     /// Client received an unexpected packet
     PacketError,
+    /// Socket was unable to write bytes
+    SocketWriteError,
 }
 
 impl TileGameState {
     fn to_score_packet(&self) -> ClientPacket {
         ClientPacket::SubmitDataPacket(GameDataPacket {
-            score: self.get_score(),
+            score: self.sim.get_score(),
             login_info: self.login_info.clone(),
         })
     }
@@ -131,81 +218,547 @@ impl TileGameState {
     }
 
     pub fn refresh_leaderboards(&mut self) -> Result<(), ClientError> {
-        let packet = ClientPacket::GetLeaderBoardsList;
-        match &mut self.client.get_mut() {
-            None => match self.connect_client() {
-                Ok(_) => self.refresh_leaderboards(),
-                Err(err) => Err(err),
-            },
+        match self.request(&ClientPacket::GetLeaderBoardsList)? {
+            ServerPacket::LeaderBoard(mut list) => {
+                list.sort_list();
+                self.leader_boards = Some(list);
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    pub fn submit_score(&mut self) -> Result<LeaderBoardList, ClientError> {
+        match self.request(&self.to_score_packet())? {
+            ServerPacket::LeaderBoard(mut list) => {
+                list.sort_list();
+                Ok(list)
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    /// Sends a packet to the server without waiting for a reply, connecting first if needed
+    fn send_packet(&mut self, packet: &ClientPacket) -> Result<(), ClientError> {
+        if self.client.get_mut().is_none() {
+            self.connect_client()?;
+        }
+        match self.client.get_mut() {
+            None => Err(ClientError::FailedToConnect),
             Some(client) => {
-                let ser = serde_json::to_string(&packet).unwrap();
-                let _ = client.write(ser.as_bytes());
-                let mut buf: [u8; 1024] = [0; 1024];
-                match client.read(&mut buf) {
-                    Ok(read_length) => {
-                        match serde_json::from_slice::<ServerPacket>(&buf[0..read_length]) {
-                            Ok(server_packet) => match server_packet {
-                                ServerPacket::LeaderBoard(mut list) => {
-                                    list.sort_list();
-                                    self.leader_boards = Some(list);
-                                    Ok(())
-                                }
-                                ServerPacket::ErrorState => Err(PacketError),
-                            },
-                            Err(_) => Err(DeserializationError),
-                        }
+                let ser = serde_json::to_string(packet).unwrap();
+                client
+                    .write_all(ser.as_bytes())
+                    .map_err(|_| SocketWriteError)
+            }
+        }
+    }
+
+    /// Reads every packet the server has sent so far, the stream must be in non-blocking mode
+    fn poll_packets(&mut self) -> Vec<ServerPacket> {
+        let mut buf: [u8; 1024] = [0; 1024];
+        if let Some(client) = self.client.get_mut() {
+            // read until the stream would block, or has been closed
+            while let Ok(read_length @ 1..) = client.read(&mut buf) {
+                self.packet_buf.extend_from_slice(&buf[0..read_length]);
+            }
+        }
+        let mut packets: Vec<ServerPacket> = self.pending_packets.drain(..).collect();
+        packets.extend(drain_packets::<ServerPacket>(&mut self.packet_buf));
+        packets
+    }
+
+    /// Switches the stream between blocking reads for requests, and non-blocking reads for live updates
+    fn set_client_nonblocking(&mut self, nonblocking: bool) {
+        if let Some(client) = self.client.get_mut() {
+            let _ = client.set_nonblocking(nonblocking);
+        }
+    }
+
+    /// Connects to the server and waits in the versus queue for an opponent
+    pub fn join_versus(&mut self) -> Result<(), ClientError> {
+        self.send_packet(&ClientPacket::JoinVersus(VersusJoin {
+            login_info: self.login_info.clone(),
+            slot_count: SLOT_COUNT.load(Ordering::Relaxed),
+        }))?;
+        self.set_client_nonblocking(true);
+        self.packet_buf.clear();
+        self.pending_packets.clear();
+        self.state = GameState::VersusQueue;
+        Ok(())
+    }
+
+    /// Leaves the versus queue, or forfeits the match if it is still being played
+    pub fn leave_versus(&mut self) {
+        let in_queue = self.state == GameState::VersusQueue;
+        let in_match = self
+            .versus
+            .as_ref()
+            .is_some_and(|versus| versus.result.is_none());
+        if in_queue || in_match {
+            let _ = self.send_packet(&ClientPacket::LeaveVersus);
+        }
+        self.versus = None;
+        self.packet_buf.clear();
+        self.pending_packets.clear();
+        self.set_client_nonblocking(false);
+    }
+
+    /// Checks if the server found an opponent, and starts the match if it did
+    pub fn poll_versus_queue(&mut self) {
+        for packet in self.poll_packets() {
+            if let ServerPacket::VersusMatchFound(versus_match) = packet {
+                SLOT_COUNT.store(versus_match.slot_count.clamp(1, 5), Ordering::Relaxed);
+                // both players seed the tile generator the same way, so they get the same tiles
+                self.start_shared_game(Difficulty::Normal, versus_match.seed);
+                self.versus = Some(VersusState::new(versus_match.opponent, self.sim.lives));
+                break;
+            }
+        }
+    }
+
+    /// Sends the status of the player to the server, throttled except for when the player runs out of lives
+    pub fn send_versus_status(&mut self) {
+        let status = VersusStatus {
+            score: self.sim.get_score(),
+            lives: self.sim.lives,
+        };
+        let should_send = self.versus.as_ref().is_some_and(|versus| {
+            versus.result.is_none()
+                && (status.lives < 0 || versus.last_update.elapsed() >= VERSUS_UPDATE_INTERVAL)
+        });
+
+        if should_send {
+            let _ = self.send_packet(&ClientPacket::VersusUpdate(status));
+            if let Some(versus) = self.versus.as_mut() {
+                versus.last_update = Instant::now();
+            }
+        }
+    }
+
+    /// Reads the status of the opponent, and the result of the match once the server decides it
+    pub fn poll_versus(&mut self) {
+        for packet in self.poll_packets() {
+            if let Some(versus) = self.versus.as_mut() {
+                match packet {
+                    ServerPacket::VersusOpponentUpdate(status) => {
+                        versus.opponent_status = status;
                     }
-                    Err(_) => Err(SocketReadError),
+                    ServerPacket::VersusGameOver { won } => {
+                        versus.result = Some(won);
+                    }
+                    _ => {}
                 }
             }
         }
     }
 
-    pub fn submit_score(&mut self) -> Result<LeaderBoardList, ClientError> {
-        let packet = self.to_score_packet().clone();
-        match &mut self.client.get_mut() {
-            None => {
-                match self.connect_client() {
-                    Ok(_) => {}
-                    Err(err) => {
-                        return Err(err);
+    /// Lets the server know the game can be spectated, only done if the player is already connected
+    fn start_live_game(&mut self) {
+        if self.client.get_mut().is_some() {
+            let packet = ClientPacket::StartLiveGame {
+                user_name: self.login_info.user_name.clone(),
+                slot_count: SLOT_COUNT.load(Ordering::Relaxed),
+            };
+            self.live = self.send_packet(&packet).is_ok();
+        }
+    }
+
+    /// Sends every event that happened since the last call to the spectators of the game
+    pub fn send_live_events(&mut self) {
+        if !self.live {
+            self.live_events.clear();
+            return;
+        }
+        self.live_events.push(LiveEvent::Status {
+            score: self.sim.get_score(),
+            lives: self.sim.lives,
+            tick: self.sim.tick,
+        });
+        let events = std::mem::take(&mut self.live_events);
+        if self.send_packet(&ClientPacket::LiveEvents(events)).is_err() {
+            self.live = false;
+        }
+    }
+
+    /// Lets the server know the game is over, so it is no longer listed for spectators
+    pub fn end_live_game(&mut self) {
+        if self.live {
+            let _ = self.send_packet(&ClientPacket::EndLiveGame);
+            self.live = false;
+        }
+    }
+
+    /// Sends a packet and waits for the reply, the stream must be in blocking mode.
+    /// Packets that are not the reply, such as live updates, are kept for the next poll rather than thrown away
+    fn request(&mut self, packet: &ClientPacket) -> Result<ServerPacket, ClientError> {
+        // a reply still queued from before was never waited for, so it can not be the reply to this packet
+        self.pending_packets
+            .extend(drain_packets::<ServerPacket>(&mut self.packet_buf));
+        self.pending_packets
+            .retain(|pending| !packet.is_reply(pending));
+        self.send_packet(packet)?;
+        let mut buf: [u8; 1024] = [0; 1024];
+        loop {
+            self.pending_packets
+                .extend(drain_packets::<ServerPacket>(&mut self.packet_buf));
+            let reply = self
+                .pending_packets
+                .iter()
+                .position(|pending| packet.is_reply(pending));
+            if let Some(packet) = reply.and_then(|index| self.pending_packets.remove(index)) {
+                return Ok(packet);
+            }
+            match self.client.get_mut() {
+                None => return Err(ClientError::FailedToConnect),
+                Some(client) => match client.read(&mut buf) {
+                    Ok(read_length @ 1..) => {
+                        self.packet_buf.extend_from_slice(&buf[0..read_length]);
                     }
-                }
-                self.submit_score()
+                    _ => return Err(SocketReadError),
+                },
             }
-            Some(client) => {
-                let ser = serde_json::to_string(&packet).unwrap();
-                let _ = client.write(ser.as_bytes());
-                let mut buf: [u8; 1024] = [0; 1024];
-                match client.read(&mut buf) {
-                    Ok(read_length) => {
-                        match serde_json::from_slice::<ServerPacket>(&buf[0..read_length]) {
-                            Ok(server_packet) => match server_packet {
-                                ServerPacket::LeaderBoard(mut list) => {
-                                    list.sort_list();
-                                    Ok(list)
-                                }
-                                ServerPacket::ErrorState => Err(PacketError),
-                            },
-                            Err(_) => Err(DeserializationError),
-                        }
+        }
+    }
+
+    /// Asks the server for every game that can currently be spectated
+    pub fn refresh_live_games(&mut self) -> Result<(), ClientError> {
+        match self.request(&ClientPacket::GetLiveGames)? {
+            ServerPacket::LiveGames(list) => {
+                self.live_games = list;
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    /// Starts spectating the live game with the given id
+    pub fn spectate(&mut self, id: u64) -> Result<(), ClientError> {
+        match self.request(&ClientPacket::Spectate(id))? {
+            ServerPacket::SpectateStarted(info) => {
+                request_new_screen_size((info.slot_count as f32 * 100.0) + 100.0, 600.0);
+                self.spectating = Some(SpectateState::new(info));
+                self.set_client_nonblocking(true);
+                self.state = GameState::Spectating;
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    /// Applies every event the server sent from the game being spectated
+    pub fn poll_spectating(&mut self) {
+        for packet in self.poll_packets() {
+            if let Some(spectating) = self.spectating.as_mut() {
+                match packet {
+                    ServerPacket::LiveEvents(events) => {
+                        events
+                            .into_iter()
+                            .for_each(|event| spectating.apply_event(event));
+                    }
+                    ServerPacket::LiveGameEnded => {
+                        spectating.ended = true;
                     }
-                    Err(_) => Err(SocketReadError),
+                    _ => {}
                 }
             }
         }
     }
 
+    /// Stops spectating the current game
+    pub fn stop_spectating(&mut self) {
+        if self.spectating.take().is_some() {
+            let _ = self.send_packet(&ClientPacket::StopSpectating);
+            self.packet_buf.clear();
+            self.pending_packets.clear();
+            self.set_client_nonblocking(false);
+        }
+    }
+
+    /// Asks the server for the challenge of the day, and starts a run using its seed and mode
+    pub fn start_daily_challenge(&mut self) -> Result<(), ClientError> {
+        match self.request(&ClientPacket::GetDailyChallenge)? {
+            ServerPacket::DailyChallenge(challenge) => {
+                // the attempt is used up as soon as the run starts, so leaving the run early can not be used to retry it
+                let attempt = ClientPacket::StartDailyAttempt(DailyAttemptPacket {
+                    day: challenge.day,
+                    login_info: self.login_info.clone(),
+                });
+                match self.request(&attempt)? {
+                    ServerPacket::DailyAttemptStarted(_) => {}
+                    _ => return Err(PacketError),
+                }
+                SLOT_COUNT.store(challenge.slot_count.clamp(1, 5), Ordering::Relaxed);
+                self.start_shared_game(challenge.difficulty, challenge.seed);
+                self.daily = Some(challenge);
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    /// Submits the score of the daily challenge that was just played, the server only ranks the first attempt of each day
+    pub fn submit_daily_score(&mut self) -> Result<(), ClientError> {
+        let day = match &self.daily {
+            None => return Ok(()),
+            Some(challenge) => challenge.day,
+        };
+        let packet = ClientPacket::SubmitDailyScore(DailyScorePacket {
+            day,
+            data: GameDataPacket {
+                score: self.sim.get_score(),
+                login_info: self.login_info.clone(),
+            },
+        });
+
+        let (mut list, ranked) = match self.request(&packet)? {
+            ServerPacket::LeaderBoard(list) => (list, true),
+            ServerPacket::DailyAttemptUsed(list) => (list, false),
+            _ => return Err(PacketError),
+        };
+        list.sort_list();
+        self.leader_boards = Some(list);
+        self.daily_ranked = Some(ranked);
+        Ok(())
+    }
+
+    /// Submits the score of the chart or preset that was just played to the leaderboard of the given category
+    pub fn submit_category_score(&mut self, category: String) -> Result<(), ClientError> {
+        let packet = ClientPacket::SubmitCategoryScore(CategoryScorePacket {
+            category,
+            data: GameDataPacket {
+                score: self.sim.get_score(),
+                login_info: self.login_info.clone(),
+            },
+        });
+
+        match self.request(&packet)? {
+            ServerPacket::LeaderBoard(mut list) => {
+                list.sort_list();
+                self.leader_boards = Some(list);
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    /// Replaces the leaderboard with the leaderboard of the given category
+    pub fn refresh_category_leaderboards(&mut self, category: String) -> Result<(), ClientError> {
+        match self.request(&ClientPacket::GetCategoryLeaderBoard(category))? {
+            ServerPacket::LeaderBoard(mut list) => {
+                list.sort_list();
+                self.leader_boards = Some(list);
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
+    /// Reads every chart from the chart directory
+    pub fn refresh_charts(&mut self) {
+        self.charts = list_charts();
+    }
+
+    /// Reads every difficulty preset from the preset directory
+    pub fn refresh_presets(&mut self) {
+        self.presets = list_presets();
+    }
+
+    /// Replaces the leaderboard with the leaderboard of the current daily challenge
+    pub fn refresh_daily_leaderboards(&mut self) -> Result<(), ClientError> {
+        match self.request(&ClientPacket::GetDailyLeaderBoard)? {
+            ServerPacket::LeaderBoard(mut list) => {
+                list.sort_list();
+                self.leader_boards = Some(list);
+                Ok(())
+            }
+            _ => Err(PacketError),
+        }
+    }
+
     pub fn goto_main_menu(&mut self) {
+        self.leave_versus();
+        self.end_live_game();
+        self.stop_spectating();
+        self.stop_music();
+        self.replay = None;
+        self.editor = None;
         self.state = GameState::MainMenu(BackgroundTileList::new());
     }
 
-    pub fn start_game(&mut self, difficulty: Difficulty, will_connect: bool) {
+    /// Starts a new run with the preset of the mode in the preset directory, using the given seed or a random one if there is no seed
+    pub fn start_game(&mut self, difficulty: Difficulty, will_connect: bool, seed: Option<u64>) {
+        let seed = seed.unwrap_or_else(rand::random);
+        let sim = Simulation::with_preset(
+            difficulty,
+            mode_preset(difficulty),
+            SLOT_COUNT.load(Ordering::Relaxed),
+            seed,
+        );
+        self.start_run(sim, will_connect);
+    }
+
+    /// Starts a run other players play with the same seed, such as a versus match or the daily challenge.
+    /// It is played with the preset the game ships with, so every player plays by the same rules
+    fn start_shared_game(&mut self, difficulty: Difficulty, seed: u64) {
+        let sim = Simulation::new(difficulty, SLOT_COUNT.load(Ordering::Relaxed), seed);
+        self.start_run(sim, true);
+    }
+
+    /// Starts a run of the preset at the given index of the preset list, using the given seed or a random one if there is no seed
+    pub fn start_preset(&mut self, index: usize, will_connect: bool, seed: Option<u64>) {
+        if let Some((_, preset)) = self.presets.get(index) {
+            let seed = seed.unwrap_or_else(rand::random);
+            let sim =
+                Simulation::from_preset(preset.clone(), SLOT_COUNT.load(Ordering::Relaxed), seed);
+            self.start_run(sim, will_connect);
+        }
+    }
+
+    /// Starts a practice run with the given tile speed and spawn interval, played at the given fraction of normal speed.
+    /// Practice runs are played offline, since they are never submitted
+    pub fn start_practice(
+        &mut self,
+        speed: f32,
+        interval: f32,
+        slow_motion: f32,
+        seed: Option<u64>,
+    ) {
+        let seed = seed.unwrap_or_else(rand::random);
+        let sim = Simulation::practice(speed, interval, SLOT_COUNT.load(Ordering::Relaxed), seed);
+        self.start_run(sim, false);
+        self.slow_motion = slow_motion;
+    }
+
+    /// Starts a run of the chart at the given index of the chart list, playing along to its song if it was loaded
+    pub fn start_chart(&mut self, index: usize, will_connect: bool, music: Option<ChartMusic>) {
+        if let Some((_, chart)) = self.charts.get(index) {
+            self.play_chart(chart.clone(), will_connect, music);
+        }
+    }
+
+    /// Starts a run of the given chart
+    fn play_chart(&mut self, chart: Chart, will_connect: bool, music: Option<ChartMusic>) {
+        SLOT_COUNT.store(chart.slot_count, Ordering::Relaxed);
+        let sim = Simulation::from_chart(chart);
+        self.start_run(sim, will_connect);
+        self.music = music.map(|mut music| {
+            music.set_start_tick(self.sim.chart_start_tick().unwrap_or_default());
+            music
+        });
+    }
+
+    /// Opens the chart editor
+    pub fn open_editor(&mut self, editor: ChartEditor) {
+        request_new_screen_size(editor.screen_width(), 600.0);
+        self.editor = Some(editor);
+        self.state = GameState::Editor;
+    }
+
+    /// Test plays the chart of the editor, the run is not submitted to any leaderboard
+    pub fn test_chart(&mut self, chart: Chart, music: Option<ChartMusic>) {
+        self.play_chart(chart, false, music);
+    }
+
+    /// Leaves a test play of a chart, and goes back to the editor
+    pub fn return_to_editor(&mut self) {
+        self.stop_music();
+        self.end_live_game();
+        if let Some(editor) = &self.editor {
+            request_new_screen_size(editor.screen_width(), 600.0);
+            self.state = GameState::Editor;
+        }
+    }
+
+    /// Returns true if the run can be paused, versus matches keep going for the opponent so they can not be
+    pub fn can_pause(&self) -> bool {
+        self.versus.is_none()
+    }
+
+    /// Returns true once the run being played can no longer change, because it is over or has gone to the score screen.
+    /// A run that ends partway through a frame is only sent to the score screen on the next frame
+    pub fn is_run_over(&self) -> bool {
+        self.sim.is_over() || self.state == GameState::ScoreScreen
+    }
+
+    /// Returns true if the run can be started again, the daily challenge only has one attempt
+    pub fn can_restart(&self) -> bool {
+        self.daily.is_none() && self.versus.is_none()
+    }
+
+    /// Pauses the run, or stops the countdown if it was resuming.
+    /// The song of a chart stops, and is picked up again from the same point once the run resumes if it is a wav song.
+    /// Keys are not read while paused, so every held slot is let go, and has to be pressed again after the countdown
+    pub fn pause(&mut self) {
+        if !self.can_pause() {
+            return;
+        }
+        for slot in 0..self.sim.slot_count {
+            self.sim.release_slot(slot);
+        }
+        if let Some(music) = self.music.as_mut() {
+            music.interrupt();
+        }
+        self.pause = Some(PauseState::Paused);
+    }
+
+    /// Starts the countdown to resuming the paused run, loading the song from where it was paused first
+    pub async fn resume(&mut self) {
+        if self.pause.is_none() {
+            return;
+        }
+        if let Some(music) = self.music.as_mut() {
+            music.prepare_resume(self.sim.tick).await;
+        }
+        self.pause = Some(PauseState::Resuming(Instant::now()));
+    }
+
+    /// Unpauses the run once the resume countdown is over, the time spent paused is never simulated
+    pub fn update_pause(&mut self) {
+        if self.pause.is_some_and(|pause| pause.is_finished()) {
+            self.pause = None;
+            self.timestep = FixedTimestep::new();
+            self.tick_budget = 0.0;
+            if let Some(music) = self.music.as_mut() {
+                music.resume(self.sim.tick);
+            }
+        }
+    }
+
+    /// Starts the run being played again from the start, with the same rules and seed
+    pub fn restart_run(&mut self) {
+        self.end_live_game();
+        let sim = self.sim.restart();
+        let will_connect = self.client.get_mut().is_some();
+        let slow_motion = self.slow_motion;
+        let music = self.music.take().map(|mut music| {
+            music.reset();
+            music
+        });
+        match sim.chart.clone() {
+            Some(chart) => self.play_chart(chart, will_connect, music),
+            None => self.start_run(sim, will_connect),
+        }
+        self.slow_motion = slow_motion;
+    }
+
+    /// Stops the song of the chart, if one is playing
+    pub fn stop_music(&mut self) {
+        if let Some(music) = self.music.take() {
+            music.stop();
+        }
+    }
+
+    /// Starts playing the given simulation
+    fn start_run(&mut self, sim: Simulation, will_connect: bool) {
+        self.stop_music();
+        let difficulty = sim.difficulty;
         *self = TileGameState {
+            sim,
+            editor: self.editor.take(),
             client: Cell::from(self.client.replace(None)),
             login_info: self.login_info.clone(),
-            state: Playing(difficulty.clone()),
-            game_start_time: SystemTime::now(),
+            state: Playing(difficulty),
             ip_address: self.ip_address.clone(),
             ..Default::default()
         };
@@ -213,136 +766,135 @@ impl TileGameState {
         if will_connect {
             let _ = self.connect_client();
         }
-        if difficulty == Difficulty::Hard {
-            self.tile_hit_count = 30;
-            self.lives = 5;
-        }
+        self.start_live_game();
 
         request_new_screen_size(
             (SLOT_COUNT.load(Ordering::Relaxed) as f32 * 100.0) + 100.0,
             600.0,
         );
-
-        for _ in 0..(SLOT_COUNT.load(Ordering::Relaxed) as usize - self.slot_press_time.len()) {
-            self.slot_press_time.push(SystemTime::UNIX_EPOCH);
-        }
     }
 
-    /// Returns the score of the player
-    pub fn get_score(&self) -> i32 {
-        self.tile_hit_count - self.slot_clicks
+    /// Presses the given slot, which hits the nearest tile in the slot as of the moment the clock was last advanced
+    pub fn press_slot(&mut self, index: usize) {
+        self.sim.press_slot_at(index as u8, self.tick_fraction());
+        self.handle_events();
     }
 
-    /// Returns a new basic slot press time, which is an array of system times representing the time since that slot has been pressed.
-    pub const fn new_slot_press_time() -> Vec<SystemTime> {
-        vec![]
-    }
-
-    /// Adds a tile to the g ame state.
-    pub fn add_tile(&mut self, speed: f32) {
-        self.tiles.push(Tile::random_new(speed));
+    /// Returns how far the simulation is into its next tick, so presses are judged between ticks. Below 1 once the simulation has been updated.
+    /// While the song of a chart is playing the song decides when ticks happen, so presses are judged on the tick
+    fn tick_fraction(&self) -> f32 {
+        match self.music {
+            None => self.tick_budget + (self.timestep.fraction() * self.slow_motion),
+            Some(_) => 0.0,
+        }
     }
 
-    /// Renders every tile in the struct.
-    pub fn draw_tiles(&self) {
-        // draw every tile
-        self.tiles.iter().for_each(|tile| {
-            tile.draw();
-        });
+    /// Releases the given slot, which lets go of any hold tile in it
+    pub fn release_slot(&mut self, index: usize) {
+        self.sim.release_slot(index as u8);
     }
 
-    /// Returns the tile speed that new tiles should.
-    fn get_tile_speed(&self) -> f32 {
-        match self.tile_hit_count {
-            ..=20 => 2.0,
-            21..=40 => {
-                const V: [f32; 2] = [2.0, 4.0];
-                match &self.state {
-                    Playing(diff) => match diff {
-                        Difficulty::Normal => 4.0,
-                        Difficulty::Hard => *V.choose(&mut rand::thread_rng()).unwrap_or(&2.0),
-                    },
-                    _ => 4.0,
-                }
+    /// Runs as many ticks of the simulation as the clock says have passed since the last frame, and reacts to what happened in them.
+    /// While the song of a chart is playing, the song is the clock instead, so tiles stay in time with it.
+    /// Slowed down practice runs only run the share of the ticks their slow motion allows, counting the part of a tick the clock is into,
+    /// so a press is never judged a whole tick or more after the tick it is made on.
+    pub fn update(&mut self) {
+        let clock_ticks = self.timestep.advance();
+        let ticks = match &self.music {
+            None => {
+                self.tick_budget += clock_ticks as f32 * self.slow_motion;
+                let ticks = self.tick_fraction().floor();
+                self.tick_budget -= ticks;
+                ticks as u64
             }
-            41.. => {
-                const V: [f32; 3] = [2.0, 4.0, 6.0];
-                match &self.state {
-                    Playing(diff) => match diff {
-                        Difficulty::Normal => 6.0,
-                        Difficulty::Hard => *V.choose(&mut rand::thread_rng()).unwrap_or(&2.0),
-                    },
-                    _ => 6.0,
-                }
+            Some(music) => music.ticks_due(self.sim.tick, clock_ticks),
+        };
+        for _ in 0..ticks {
+            if let Some(music) = self.music.as_mut() {
+                music.start_if_due(self.sim.tick);
             }
+            self.sim.step();
         }
+        self.handle_events();
     }
 
-    /// Updates tile spawn time based on players tile hit count.
-    fn update_tile_spawn_time(&mut self) {
-        match self.tile_hit_count {
-            ..=-1 => {
-                self.tile_spawn_time = 1.75;
-            }
-            0..=5 => {
-                self.tile_spawn_time = 1.5;
-            }
-            6..=10 => {
-                self.tile_spawn_time = 1.25;
-            }
-            11..=20 => {
-                self.tile_spawn_time = 1.0;
+    /// Plays the sounds of everything that has happened in the simulation, and queues it up for spectators
+    fn handle_events(&mut self) {
+        for event in self.sim.drain_events() {
+            play_event_sound(&event);
+            if let Some(judgement) = event.judgement() {
+                self.judgement = Some((judgement, self.sim.tick));
             }
-            21..=30 => {
-                self.tile_spawn_time = 0.75;
+            if let Some(live_event) = event.to_live_event() {
+                self.live_events.push(live_event);
             }
-            31..=50 => {
-                self.tile_spawn_time = 0.5;
+        }
+    }
+
+    /// Saves the run that was just played as a replay
+    pub fn save_replay(&mut self) {
+        let replay = Replay::from_simulation(&self.sim, self.login_info.user_name.clone());
+        match save_replay(&replay) {
+            Ok(path) => {
+                self.replay_path = Some(path);
             }
-            51.. => {
-                self.tile_spawn_time = 0.25;
+            Err(err) => {
+                println!("{:?}", err);
             }
         }
     }
 
-    /// Ticks all the tiles in the game state.
-    pub fn tick_game_state(&mut self) {
-        self.update_tile_spawn_time(); // update tile spawning rate
-
-        self.tiles.iter_mut().for_each(|tile| tile.tick()); // tick every tile
-
-        let time = SystemTime::now()
-            .duration_since(self.time_since_tile)
-            .unwrap()
-            .as_secs_f32();
+    /// Reads every saved replay from the replay directory
+    pub fn refresh_replays(&mut self) {
+        self.replays = list_replays();
+    }
 
-        if time >= self.tile_spawn_time {
-            // decide if we need to spawn a new tile
-            self.add_tile({
-                self.get_tile_speed() // get a tile speed from the game state
-            });
-            self.time_since_tile = SystemTime::now(); // update the time it has been since the last tile was spawned
+    /// Starts watching the saved replay at the given index of the replay list, replays recorded with an older simulation can not be watched
+    pub fn watch_replay(&mut self, index: usize) {
+        if let Some((_, replay)) = self
+            .replays
+            .get(index)
+            .filter(|(_, replay)| replay.is_current())
+        {
+            request_new_screen_size((replay.slot_count as f32 * 100.0) + 100.0, 600.0);
+            self.replay = Some(ReplayPlayer::new(replay.clone()));
+            self.judgement = None;
+            self.timestep = FixedTimestep::new();
+            self.state = GameState::WatchingReplay;
         }
     }
 
-    /// Removes all the tiles that are off screen.
-    pub fn cleanup_tiles(&mut self) {
-        self.tiles = self // set the tile list to a new tile list clone
-            .tiles
-            .clone()
-            .into_iter()
-            .filter(|tile| {
-                // filter out every tile that is below the screen margin
-                let distance_state = tile.distance < 600.0; // state determining if the tile is off bottom of screen
-
-                if !distance_state {
-                    // if the tile is, then play a sound, reduce the score, and remove a life
-                    self.lives -= 1;
-                    play_sound_once(ANTI_TICK_SOUND.get().unwrap());
+    /// Plays the replay being watched for as long as the clock says has passed since the last frame
+    pub fn update_replay(&mut self) {
+        let ticks = self.timestep.advance();
+        if let Some(replay) = self.replay.as_mut() {
+            for event in replay.update(ticks) {
+                play_event_sound(&event);
+                if let Some(judgement) = event.judgement() {
+                    self.judgement = Some((judgement, replay.sim.tick));
                 }
+            }
+        }
+    }
 
-                distance_state
-            })
-            .collect();
+    /// Renders every tile in the simulation.
+    pub fn draw_tiles(&self) {
+        // draw every tile
+        self.sim.tiles.iter().for_each(|tile| {
+            tile.draw();
+        });
+    }
+}
+
+/// Plays the sound that goes along with something that happened in the simulation
+fn play_event_sound(event: &SimEvent) {
+    match event {
+        SimEvent::TileHit { .. } | SimEvent::HoldStarted { .. } | SimEvent::LifeGained { .. } => {
+            play_sound_once(TICK_SOUND.get().unwrap());
+        }
+        SimEvent::LifeLost { .. } | SimEvent::HoldBroken { .. } => {
+            play_sound_once(ANTI_TICK_SOUND.get().unwrap());
+        }
+        _ => {}
     }
 }
@@ -0,0 +1,143 @@
+//! hud is a source file containing the drawing code shared by every view of a game being played
+#![warn(missing_docs)]
+
+use crate::tile::TILE_WIDTH;
+use crate::{get_color, HEART_ICON};
+use cr_tile_game_common::judgement::{Judgement, JudgementCounts};
+use cr_tile_game_common::tile::{HIT_BAR, HIT_DISTANCE};
+use cr_tile_game_common::timestep::TICKS_PER_SECOND;
+use macroquad::prelude::*;
+
+/// The number of ticks a judgement stays on screen after it is given
+pub const JUDGEMENT_TICKS: u64 = TICKS_PER_SECOND as u64 / 2;
+
+/// Draws a heart for every life the player has, in the column to the right of the slots
+pub fn draw_lives(lives: i32, slot_count: u8) {
+    let heart = HEART_ICON.get().unwrap();
+    for a in 0..lives {
+        draw_texture(
+            heart,
+            slot_count as f32 * TILE_WIDTH,
+            100.0 + (heart.height() * a as f32),
+            WHITE,
+        );
+    }
+}
+
+/// Draws the hit bar, the key for each slot under it, and the lines between each slot
+pub fn draw_hit_bar(slot_count: u8) {
+    let bar_width = TILE_WIDTH * slot_count as f32;
+    draw_rectangle(0.0, HIT_BAR, bar_width, HIT_DISTANCE, BLACK); // draw the hit bar
+    for slot in 0..slot_count {
+        let x_value = (slot as f32 * TILE_WIDTH) // the respective x value of every slot
+            + (TILE_WIDTH / 2.0) // add half a tile width so we can put the text in the middle of a slot
+            - 5.0; // slight magic number to make the text more centered.
+        draw_text(
+            {
+                // render text based on the slot its in
+                match slot {
+                    0 => "Q",
+                    1 => "W",
+                    2 => "E",
+                    3 => "R",
+                    4 => "T",
+                    _ => "???", // unknown slot number ???
+                }
+            },
+            x_value,
+            590.0,
+            20.0,
+            BLACK,
+        );
+        draw_rectangle(
+            (slot as f32 * TILE_WIDTH) - 1.0,
+            0.0,
+            2.0,
+            screen_height(),
+            DARKGRAY,
+        );
+    }
+    draw_rectangle(
+        ((slot_count) as f32 * TILE_WIDTH) - 1.0,
+        0.0,
+        2.0,
+        screen_height(),
+        DARKGRAY,
+    );
+}
+
+/// Draws the part of the hit bar belonging to a slot in the color of that slot, used while the slot is pressed
+pub fn draw_slot_press(index: usize, slot_count: u8) {
+    let bar_width = TILE_WIDTH * slot_count as f32;
+    let x_value = index as f32 * (bar_width / slot_count as f32);
+    draw_rectangle(
+        x_value,
+        HIT_BAR,
+        bar_width / slot_count as f32,
+        HIT_DISTANCE,
+        get_color(index),
+    );
+}
+
+/// Returns the color a judgement is drawn in
+pub fn judgement_color(judgement: Judgement) -> Color {
+    match judgement {
+        Judgement::Perfect => GOLD,
+        Judgement::Great => DARKGREEN,
+        Judgement::Good => DARKBLUE,
+        Judgement::Miss => RED,
+    }
+}
+
+/// Draws the last judgement the player was given above the middle of the hit bar, fading out as it gets older
+pub fn draw_judgement(last_judgement: Option<(Judgement, u64)>, tick: u64, slot_count: u8) {
+    let Some((judgement, given_tick)) = last_judgement else {
+        return;
+    };
+    let age = match tick.checked_sub(given_tick) {
+        Some(age) if age < JUDGEMENT_TICKS => age,
+        _ => return,
+    };
+    let font_size = 40.0;
+    let text = judgement.name();
+    let size = measure_text(text, None, font_size as u16, 1.0);
+    let mut color = judgement_color(judgement);
+    color.a = 1.0 - (age as f32 / JUDGEMENT_TICKS as f32);
+    draw_text(
+        text,
+        (TILE_WIDTH * slot_count as f32 - size.width) / 2.0,
+        HIT_BAR - 100.0,
+        font_size,
+        color,
+    );
+}
+
+/// Draws the number of times each judgement was given on a single line
+pub fn draw_judgement_counts(counts: &JudgementCounts, x: f32, y: f32) {
+    let mut x = x;
+    for judgement in Judgement::ALL {
+        let text = format!("{}: {}", judgement.name(), counts.get(judgement));
+        draw_text(&text, x, y, 20.0, judgement_color(judgement));
+        x += measure_text(&text, None, 20, 1.0).width + 10.0;
+    }
+}
+
+/// Draws the combo and the multiplier it gives above the judgement, while there is a combo
+pub fn draw_combo(combo: u32, multiplier: i32, slot_count: u8) {
+    if combo == 0 {
+        return;
+    }
+    let font_size = 30.0;
+    let text = match multiplier {
+        1 => format!("{} combo", combo),
+        _ => format!("{} combo x{}", combo, multiplier),
+    };
+    let size = measure_text(&text, None, font_size as u16, 1.0);
+    draw_text(
+        &text,
+        (TILE_WIDTH * slot_count as f32 - size.width) / 2.0,
+        HIT_BAR - 140.0,
+        font_size,
+        DARKGRAY,
+    );
+}
@@ -0,0 +1,72 @@
+//! lan_discovery is a source file containing the code to find game services on the local network
+#![warn(missing_docs)]
+
+use cr_tile_game_common::discovery::{DiscoveryPacket, DISCOVERY_PORT};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// How long the client waits for services to answer a discovery broadcast
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A game service that answered a discovery broadcast
+pub struct LanServer {
+    /// The name the service advertised
+    pub name: String,
+    /// The address that can be placed directly into the ip field, e.g. "192.168.1.86:8114"
+    pub address: String,
+}
+
+/// Broadcasts a discovery request on the local network, and returns every service that answered before the timeout.
+/// This blocks for the entire duration of the timeout.
+pub fn find_lan_servers(timeout: Duration) -> Vec<LanServer> {
+    let mut servers = vec![];
+
+    let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("{}", err);
+            return servers;
+        }
+    };
+
+    let request = serde_json::to_vec(&DiscoveryPacket::Request).unwrap();
+    if let Err(err) = socket
+        .set_broadcast(true)
+        .and_then(|_| socket.send_to(&request, (Ipv4Addr::BROADCAST, DISCOVERY_PORT)))
+    {
+        eprintln!("{}", err);
+        return servers;
+    }
+
+    let deadline = Instant::now() + timeout;
+    let mut buf: [u8; 1024] = [0; 1024];
+    loop {
+        // read until the deadline passes, a zero read timeout would block forever so bail out before that can happen
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || socket.set_read_timeout(Some(remaining)).is_err() {
+            break;
+        }
+
+        match socket.recv_from(&mut buf) {
+            Ok((read_length, src)) => {
+                if let Ok(DiscoveryPacket::Response(response)) =
+                    serde_json::from_slice::<DiscoveryPacket>(&buf[0..read_length])
+                {
+                    let server = LanServer {
+                        name: response.name,
+                        address: format!("{}:{}", src.ip(), response.port),
+                    };
+                    if !servers.contains(&server) {
+                        servers.push(server);
+                    }
+                }
+            }
+            Err(_) => {
+                break;
+            }
+        }
+    }
+
+    servers
+}
@@ -1,8 +1,17 @@
 #![windows_subsystem = "windows"]
 
-use crate::game_settings::GameSettings;
+use crate::chart::chart_dir;
+use crate::editor::{ChartEditor, EditorAction};
+use crate::game_settings::{
+    GameSettings, MAX_AUDIO_OFFSET, MAX_PRACTICE_INTERVAL, MAX_PRACTICE_SPEED, MIN_AUDIO_OFFSET,
+    MIN_PRACTICE_INTERVAL, MIN_PRACTICE_SPEED, MIN_SLOW_MOTION,
+};
 use crate::game_state::{Difficulty, GameState, TileGameState};
-use crate::tile::TILE_WIDTH;
+use crate::lan_discovery::{find_lan_servers, LanServer, DISCOVERY_TIMEOUT};
+use crate::music::{load_chart_music, ChartMusic};
+use crate::pause::PauseState;
+use crate::replay::SEEK_TICKS;
+use crate::tile::{DrawTile, TILE_WIDTH};
 use cr_tile_game_common::packet::LoginInfo;
 use macroquad::audio::{load_sound_from_bytes, play_sound_once, set_sound_volume, Sound};
 use macroquad::hash;
@@ -10,28 +19,30 @@ use macroquad::prelude::*;
 use macroquad::ui::root_ui;
 use std::iter::Iterator;
 use std::process::exit;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::OnceLock;
-use std::thread::sleep;
-use std::time::{Duration, SystemTime};
 
 mod background_elements;
+mod chart;
+mod editor;
 mod game_settings;
 mod game_state;
+mod hud;
+mod lan_discovery;
+mod music;
+mod pause;
+mod preset;
+mod replay;
+mod spectate;
 mod tile;
+mod user_files;
+mod versus;
 
-/// The height that the bar to hit tiles is on the screen
-const HIT_BAR: f32 = 550.0;
-/// The height of the hit bar, larger hit distance would make hitting tiles easier
-const HIT_DISTANCE: f32 = 25.0;
-/// The location on the y axis representing the middle of the hit bar, used for distance calculations
-const MIDDLE_BAR: f32 = HIT_BAR + (HIT_DISTANCE / 2.0);
 /// The number of slots for tiles, the width of the game
 static SLOT_COUNT: AtomicU8 = AtomicU8::new(3);
 /// The colors representing each tile hit bar
 const COLORS: [Color; 5] = [ORANGE, BLUE, PURPLE, PINK, YELLOW];
-/// The duration in seconds representing how long a key press is held
-const HIT_LENGTH: f32 = 0.125;
 /// The keybindings relating to each slot.
 const KEY_BINDS: [KeyCode; 5] = [KeyCode::Q, KeyCode::W, KeyCode::E, KeyCode::R, KeyCode::T];
 
@@ -60,6 +71,11 @@ fn get_hit_bar_width() -> f32 {
     TILE_WIDTH * SLOT_COUNT.load(Ordering::Relaxed) as f32
 }
 
+/// Parses the seed typed on the main menu, an empty or invalid seed means a random seed should be used
+fn parse_seed(input: &str) -> Option<u64> {
+    input.trim().parse().ok()
+}
+
 fn window_conf() -> Conf {
     Conf {
         window_title: GIT_DESCRIBE.to_string(),
@@ -80,7 +96,14 @@ async fn main() {
         ..Default::default()
     };
     let mut tick_vol = game_settings.volume;
+    let mut audio_offset = game_settings.audio_offset;
     let mut will_connect = false;
+    let mut lan_servers: Vec<LanServer> = vec![];
+    let mut server_name = game_settings.last_server.clone();
+    let mut server_selection = game_settings
+        .get_server_index(&game_settings.last_server)
+        .unwrap_or(0);
+    let mut seed_input = String::new();
     SLOT_COUNT.store(game_settings.slot_count, Ordering::Relaxed);
 
     // load textures and sounds
@@ -120,8 +143,9 @@ async fn main() {
     request_new_screen_size(400.0, 600.0);
 
     loop {
-        // quit game key bind
-        if is_key_pressed(KeyCode::Escape) {
+        // quit game key bind, escape pauses a run instead when it can be paused
+        let can_pause = matches!(state.state, GameState::Playing(_)) && state.can_pause();
+        if is_key_pressed(KeyCode::Escape) && !can_pause {
             game_settings.save();
             exit(0);
         }
@@ -136,7 +160,7 @@ async fn main() {
                         Vec2::from_slice(&[(screen_width() / 2.0) - 25.0, screen_height() / 2.0]),
                         "Normal Mode",
                     ) {
-                        state.start_game(Difficulty::Normal, will_connect);
+                        state.start_game(Difficulty::Normal, will_connect, parse_seed(&seed_input));
                     }
                     if root_ui().button(
                         Vec2::from_slice(&[
@@ -145,7 +169,7 @@ async fn main() {
                         ]),
                         "Hard Mode",
                     ) {
-                        state.start_game(Difficulty::Hard, will_connect);
+                        state.start_game(Difficulty::Hard, will_connect, parse_seed(&seed_input));
                     }
                     if root_ui().button(
                         Vec2::from_slice(&[
@@ -156,6 +180,111 @@ async fn main() {
                     ) {
                         state.state = GameState::Leaderboards;
                     }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 75.0,
+                        ]),
+                        "Versus Mode",
+                    ) {
+                        if let Err(err) = state.join_versus() {
+                            println!("{:?}", err);
+                        }
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 100.0,
+                        ]),
+                        "Spectate",
+                    ) {
+                        state.state = GameState::SpectateList;
+                        if let Err(err) = state.refresh_live_games() {
+                            println!("{:?}", err);
+                        }
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 125.0,
+                        ]),
+                        "Daily Challenge",
+                    ) {
+                        if let Err(err) = state.start_daily_challenge() {
+                            println!("{:?}", err);
+                        }
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 150.0,
+                        ]),
+                        "Watch Replay",
+                    ) {
+                        state.refresh_replays();
+                        state.state = GameState::ReplayList;
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 175.0,
+                        ]),
+                        "Play Chart",
+                    ) {
+                        state.refresh_charts();
+                        state.state = GameState::ChartList;
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 200.0,
+                        ]),
+                        "Presets",
+                    ) {
+                        state.refresh_presets();
+                        state.state = GameState::PresetList;
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[(screen_width() / 2.0) - 125.0, screen_height() / 2.0]),
+                        "Practice",
+                    ) {
+                        state.state = GameState::PracticeSetup;
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 225.0,
+                        ]),
+                        "Zen Mode",
+                    ) {
+                        state.start_game(Difficulty::Zen, will_connect, parse_seed(&seed_input));
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 250.0,
+                        ]),
+                        "Time Attack",
+                    ) {
+                        state.start_game(
+                            Difficulty::TimeAttack,
+                            will_connect,
+                            parse_seed(&seed_input),
+                        );
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[
+                            (screen_width() / 2.0) - 25.0,
+                            (screen_height() / 2.0) + 275.0,
+                        ]),
+                        "Sudden Death",
+                    ) {
+                        state.start_game(
+                            Difficulty::SuddenDeath,
+                            will_connect,
+                            parse_seed(&seed_input),
+                        );
+                    }
                 }
 
                 if root_ui().button(None, "Quit") {
@@ -166,6 +295,13 @@ async fn main() {
                 }
                 root_ui().slider(hash!(), "Volume", 0.0..1.0, &mut tick_vol); // volume slider
                 game_settings.volume = tick_vol;
+                root_ui().slider(
+                    hash!(),
+                    "Audio offset (ms)",
+                    MIN_AUDIO_OFFSET..MAX_AUDIO_OFFSET,
+                    &mut audio_offset,
+                );
+                game_settings.audio_offset = audio_offset;
                 let slot_count_load = SLOT_COUNT.load(Ordering::Relaxed);
 
                 // block for changing slot count
@@ -175,7 +311,7 @@ async fn main() {
                         game_settings.slot_count = SLOT_COUNT.load(Ordering::Relaxed);
                     }
                     draw_text(
-                        &format!("{}", SLOT_COUNT.load(Ordering::Relaxed)),
+                        format!("{}", SLOT_COUNT.load(Ordering::Relaxed)),
                         50.0,
                         80.0,
                         20.0,
@@ -200,6 +336,56 @@ async fn main() {
                     // connection to internet checkbox
                     root_ui().checkbox(hash!(), "Play Online ?", &mut will_connect);
 
+                    // seed to start a run from, left empty for a random seed
+                    root_ui().input_text(hash!(), "Seed", &mut seed_input);
+
+                    // saved server selector, picking a server fills in the login info and ip below
+                    if !game_settings.servers.is_empty() {
+                        let names = game_settings
+                            .servers
+                            .iter()
+                            .map(|server| match server.favorite {
+                                true => format!("* {}", server.name),
+                                false => server.name.clone(),
+                            })
+                            .collect::<Vec<String>>();
+                        let names = names
+                            .iter()
+                            .map(|name| name.as_str())
+                            .collect::<Vec<&str>>();
+
+                        // keep showing the last used server, the list can be reordered or shortened by the buttons below
+                        if let Some(index) =
+                            game_settings.get_server_index(&game_settings.last_server)
+                        {
+                            server_selection = index;
+                        }
+                        server_selection = server_selection.min(names.len() - 1);
+                        let previous_selection = server_selection;
+                        root_ui().combo_box(hash!(), "Server", &names, &mut server_selection);
+
+                        // only a server picked by the player is applied, so the typed in address and login are kept otherwise
+                        if server_selection != previous_selection {
+                            let server = game_settings.servers[server_selection].clone();
+                            game_settings.apply_server(&server);
+                            server_name = server.name;
+                            state.ip_address = game_settings.ip_address.clone();
+                            state.login_info.user_name = game_settings.username.clone();
+                            state.login_info.key = game_settings.key.clone();
+                        }
+                    }
+
+                    root_ui().input_text(hash!(), "Server name", &mut server_name);
+                    if root_ui().button(None, "Save server") && !server_name.is_empty() {
+                        game_settings.save_server(&server_name);
+                    }
+                    if root_ui().button(None, "Favorite") {
+                        game_settings.toggle_favorite(&server_name);
+                    }
+                    if root_ui().button(None, "Delete server") {
+                        game_settings.delete_server(&server_name);
+                    }
+
                     // login info
                     root_ui().input_text(hash!(), "Name", &mut state.login_info.user_name);
                     game_settings.username = state.login_info.user_name.clone();
@@ -208,6 +394,16 @@ async fn main() {
 
                     // ip to connect to
                     root_ui().input_text(hash!(), "IP", &mut state.ip_address);
+
+                    // services found on the local network, clicking one fills in the ip field
+                    if root_ui().button(None, "Find LAN servers") {
+                        lan_servers = find_lan_servers(DISCOVERY_TIMEOUT);
+                    }
+                    for server in &lan_servers {
+                        if root_ui().button(None, format!("{} ({})", server.name, server.address)) {
+                            state.ip_address = server.address.clone();
+                        }
+                    }
                     game_settings.ip_address = state.ip_address.clone();
                 }
 
@@ -229,8 +425,11 @@ async fn main() {
 
                 // background tiles
                 {
+                    let ticks = state.timestep.advance();
                     if let GameState::MainMenu(bg_tile_list) = &mut state.state {
-                        bg_tile_list.step();
+                        for _ in 0..ticks {
+                            bg_tile_list.step();
+                        }
                         bg_tile_list.draw_all();
                     }
                 }
@@ -241,31 +440,62 @@ async fn main() {
                 set_sound_volume(TICK_SOUND.get().unwrap(), tick_vol);
                 set_sound_volume(ANTI_TICK_SOUND.get().unwrap(), tick_vol);
 
-                // stop the game when the lives are less than 0
-                if state.lives < 0 {
+                // pause key bind, pressing it while resuming pauses the run again
+                if is_key_pressed(KeyCode::P) || is_key_pressed(KeyCode::Escape) {
+                    match state.pause {
+                        Some(PauseState::Paused) => state.resume().await,
+                        _ => state.pause(),
+                    }
+                }
+                state.update_pause();
+                let paused = state.pause.is_some();
+
+                // keep the opponent up to date, and find out if they have run out of lives
+                if state.versus.is_some() {
+                    state.send_versus_status();
+                    state.poll_versus();
+                }
+                let versus_decided = state
+                    .versus
+                    .as_ref()
+                    .is_some_and(|versus| versus.result.is_some());
+
+                // stop the game when the lives are less than 0, or when the versus match has been decided
+                if state.sim.is_over() || versus_decided {
                     state.state = GameState::ScoreScreen;
+                    state.stop_music();
+                    state.send_live_events();
+                    state.end_live_game();
 
-                    if state.client.get_mut().is_some() {
-                        match state.submit_score() {
-                            Ok(list) => {
-                                state.leader_boards = Some(list);
+                    // versus matches and practice runs are not ranked
+                    if state.client.get_mut().is_some()
+                        && state.versus.is_none()
+                        && state.editor.is_none()
+                        && difficulty.is_ranked()
+                    {
+                        if state.daily.is_some() {
+                            if let Err(err) = state.submit_daily_score() {
+                                println!("{:?}", err);
                             }
-                            Err(err) => {
+                        } else if let Some(category) = state.sim.leader_board_category() {
+                            if let Err(err) = state.submit_category_score(category) {
                                 println!("{:?}", err);
                             }
+                        } else {
+                            match state.submit_score() {
+                                Ok(list) => {
+                                    state.leader_boards = Some(list);
+                                }
+                                Err(err) => {
+                                    println!("{:?}", err);
+                                }
+                            }
                         }
                     }
-                    state.game_end_time = SystemTime::now();
                 }
 
-                // draw each heart for every life the player has
-                for a in 0..state.lives {
-                    draw_texture(
-                        HEART_ICON.get().unwrap(),
-                        SLOT_COUNT.load(Ordering::Relaxed) as f32 * 100.0,
-                        100.0 + (HEART_ICON.get().unwrap().height() * a as f32),
-                        WHITE,
-                    );
+                if difficulty.has_lives() {
+                    hud::draw_lives(state.sim.lives, SLOT_COUNT.load(Ordering::Relaxed));
                 }
 
                 match state.client.get_mut() {
@@ -283,17 +513,47 @@ async fn main() {
                 }
 
                 draw_text(
-                    &format!("Score: {}", state.get_score()),
+                    format!("Score: {}", state.sim.get_score()),
                     bar_width,
                     50.0,
                     20.0,
                     BLACK,
                 );
 
+                if let Some(versus) = &state.versus {
+                    versus.draw_opponent(bar_width, 66.0);
+                }
+
+                if let Some(time_left) = state.sim.time_left() {
+                    draw_text(
+                        format!("Time left: {:.0}s", time_left.ceil()),
+                        bar_width,
+                        90.0,
+                        20.0,
+                        BLACK,
+                    );
+                }
+                // zen and practice runs have no lives, so they only end when the player ends them
+                if matches!(difficulty, Difficulty::Zen | Difficulty::Practice) {
+                    draw_text("Enter: end run", bar_width, 90.0, 20.0, BLACK);
+                    if is_key_pressed(KeyCode::Enter) {
+                        state.sim.end();
+                    }
+                }
+                if state.slow_motion < 1.0 {
+                    draw_text(
+                        format!("Slow motion: {:.0}%", state.slow_motion * 100.0),
+                        bar_width,
+                        114.0,
+                        20.0,
+                        BLACK,
+                    );
+                }
+
                 #[cfg(debug_assertions)] // debug info
                 {
                     draw_text(
-                        &format!("DEBUG TST: {}", state.tile_spawn_time),
+                        format!("DEBUG TST: {}", state.sim.tile_spawn_time),
                         220.0,
                         70.0,
                         20.0,
@@ -301,15 +561,15 @@ async fn main() {
                     );
 
                     if is_key_pressed(KeyCode::G) {
-                        state.add_tile(4.0);
+                        state.sim.add_tile(4.0);
                     }
 
                     if is_key_pressed(KeyCode::H) {
-                        state.lives -= 1;
+                        state.sim.lives -= 1;
                     }
 
                     if is_key_pressed(KeyCode::A) {
-                        state.tile_hit_count += 10;
+                        state.sim.tile_hit_count += 10;
                     }
                 }
 
@@ -323,157 +583,163 @@ async fn main() {
                     );
                 }
 
+                // catch the simulation up to now before taking input, so key presses are judged against where the tiles are now.
+                // nothing changes the run once it is over, so the score submitted and the replay saved are of the run as it ended
+                if !paused && !state.is_run_over() {
+                    state.update();
+                }
+                let takes_input = !paused && !state.is_run_over();
+
                 // draw hit bar and take input for hit bar
                 {
                     let slot_count = SLOT_COUNT.load(Ordering::Relaxed);
-                    draw_rectangle(0.0, HIT_BAR, bar_width, HIT_DISTANCE, BLACK); // draw the hit bar
-                    for slot in 0..slot_count {
-                        let x_value = (slot as f32 * TILE_WIDTH) // the respective x value of every slot
-                            + (TILE_WIDTH / 2.0) // add half a tile width so we can put the text in the middle of a slot
-                            - 5.0; // slight magic number to make the text more centered.
-                        draw_text(
-                            {
-                                // render text based on the slot its in
-                                match slot {
-                                    0 => "Q",
-                                    1 => "W",
-                                    2 => "E",
-                                    3 => "R",
-                                    4 => "T",
-                                    _ => "???", // unknown slot number ???
-                                }
-                            },
-                            x_value,
-                            590.0,
-                            20.0,
-                            BLACK,
-                        );
-                        draw_rectangle(
-                            (slot as f32 * TILE_WIDTH) - 1.0,
-                            0.0,
-                            2.0,
-                            screen_height(),
-                            DARKGRAY,
-                        );
-                    }
-                    draw_rectangle(
-                        ((slot_count) as f32 * TILE_WIDTH) - 1.0,
-                        0.0,
-                        2.0,
-                        screen_height(),
-                        DARKGRAY,
-                    );
+                    hud::draw_hit_bar(slot_count);
 
-                    // slot press time updates for key presses
-                    for (index, key) in KEY_BINDS.iter().enumerate() {
+                    // slot presses for key presses, keys do nothing while paused or once the run is over
+                    for (index, key) in KEY_BINDS.iter().enumerate().filter(|_| takes_input) {
                         // iterate through every key bind, checking if the respective key was pressed
                         if is_key_pressed(*key) && slot_count_check(index) {
-                            match state.slot_press_time.get_mut(index) {
-                                None => {
-                                    // if no slot press time exists for this key, add one just in-case
-                                    state.slot_press_time.push(SystemTime::now());
-                                }
-                                Some(time) => {
-                                    // if a time does exist, update it
-                                    *time = SystemTime::now();
-                                }
-                            }
-                            state.slot_clicks += 1; // increment the slot click count when a slot is clicked
+                            state.press_slot(index);
                         }
-                    }
-
-                    let slot_time_iter = state.slot_press_time.iter();
-                    let combined = slot_time_iter
-                        .enumerate()
-                        .map(|(index, time)| (get_color(index), time)); // create an iterator that has every color and its respective time it was clicked
-
-                    // iterate over each slot time and color, and if the time listed as the last time the slot was pressed is less than HIT_LENGTH in time, then draw the respective color.
-                    combined.enumerate().for_each(|(index, (color, time))| {
-                        if SystemTime::now()
-                            .duration_since(*time)
-                            .unwrap()
-                            .as_secs_f32()
-                            <= {
-                                // change the hit length depending on difficulty, experimental??
-                                match difficulty {
-                                    Difficulty::Normal => HIT_LENGTH,
-                                    Difficulty::Hard => HIT_LENGTH / 10.0,
-                                }
-                            }
-                        {
-                            // draw each slot bar
-                            let x_value = index as f32 * (bar_width / slot_count as f32);
-                            draw_rectangle(
-                                x_value,
-                                HIT_BAR,
-                                bar_width / slot_count as f32,
-                                HIT_DISTANCE,
-                                color,
-                            );
-
-                            // remove tiles which are hit and hit on the slot that is hitting them.
-                            state.tiles = state
-                                .tiles
-                                .clone()
-                                .into_iter()
-                                .filter(|tile| {
-                                    let hit_state = tile.is_hit(index as u8); // state representing if the given tile is hit
-
-                                    if hit_state {
-                                        // if the tile was hit, increment the hit count
-                                        state.tile_hit_count += 2;
-                                        play_sound_once(TICK_SOUND.get().unwrap())
-                                    }
-
-                                    !hit_state // do not keep hit tiles, thus filtering them out when they are hit
-                                })
-                                .collect();
+                        if is_key_released(*key) && slot_count_check(index) {
+                            state.release_slot(index);
                         }
-                    });
+                    }
                 }
 
                 // state management
                 {
+                    // draw each slot bar that is still pressed
+                    for index in 0..state.sim.slot_press_tick.len() {
+                        if state.sim.is_slot_active(index) {
+                            hud::draw_slot_press(index, SLOT_COUNT.load(Ordering::Relaxed));
+                        }
+                    }
+
                     state.draw_tiles();
-                    state.tick_game_state();
-                    state.cleanup_tiles();
+                    hud::draw_combo(
+                        state.sim.combo,
+                        state.sim.multiplier(),
+                        SLOT_COUNT.load(Ordering::Relaxed),
+                    );
+                    hud::draw_judgement(
+                        state.judgement,
+                        state.sim.tick,
+                        SLOT_COUNT.load(Ordering::Relaxed),
+                    );
+                    state.send_live_events();
                 }
 
                 // draw border around game so it looks pretty :)
                 draw_rectangle_lines(0.0, 0.0, screen_width(), screen_height(), 8.0, BLACK);
+
+                if let Some(pause) = state.pause {
+                    pause.draw();
+                    if let PauseState::Paused = pause {
+                        if root_ui().button(
+                            Vec2::from_slice(&[screen_width() / 2.0 - 61.0, screen_height() / 2.0]),
+                            "Resume",
+                        ) {
+                            state.resume().await;
+                        }
+                        if state.can_restart()
+                            && root_ui().button(
+                                Vec2::from_slice(&[
+                                    screen_width() / 2.0 - 61.0,
+                                    screen_height() / 2.0 + 25.0,
+                                ]),
+                                "Restart",
+                            )
+                        {
+                            state.restart_run();
+                        }
+                        if root_ui().button(
+                            Vec2::from_slice(&[
+                                screen_width() / 2.0 - 61.0,
+                                screen_height() / 2.0 + 50.0,
+                            ]),
+                            "Quit to menu",
+                        ) {
+                            if state.editor.is_some() {
+                                state.return_to_editor();
+                            } else {
+                                state.goto_main_menu();
+                                request_new_screen_size(400.0, 600.0);
+                            }
+                        }
+                    }
+                }
             }
             GameState::ScoreScreen => {
                 clear_background(GRAY);
                 draw_text(
-                    &format!("Final score: {}", state.get_score()),
+                    format!("Final score: {}", state.sim.get_score()),
                     50.0,
                     50.0,
                     20.0,
                     BLACK,
                 );
                 draw_text("Press B to go back to main menu", 50.0, 70.0, 20.0, BLACK);
-                let time_survived = state
-                    .game_end_time
-                    .duration_since(state.game_start_time)
-                    .unwrap_or_default();
+                if state.versus.is_some() {
+                    state.poll_versus();
+                }
+                if let Some(versus) = &state.versus {
+                    versus.draw_result(50.0, 190.0);
+                }
+                if state.daily.is_some() {
+                    draw_text(
+                        match state.daily_ranked {
+                            None => "Daily challenge score was not submitted",
+                            Some(true) => "Daily challenge score ranked!",
+                            Some(false) => "Daily challenge already played today, not ranked",
+                        },
+                        50.0,
+                        190.0,
+                        20.0,
+                        BLACK,
+                    );
+                }
+                hud::draw_judgement_counts(&state.sim.judgements, 50.0, 150.0);
+                draw_text(
+                    format!("Max combo: {}", state.sim.max_combo),
+                    50.0,
+                    170.0,
+                    20.0,
+                    BLACK,
+                );
                 draw_text(
-                    &format!("Time survived: {:.2}s", time_survived.as_secs_f32()),
+                    format!("Time survived: {:.2}s", state.sim.time_survived()),
                     50.0,
                     90.0,
                     20.0,
                     BLACK,
                 );
                 draw_text(
-                    &format!("Slot count: {}", SLOT_COUNT.load(Ordering::Relaxed)),
+                    format!("Slot count: {}", SLOT_COUNT.load(Ordering::Relaxed)),
                     50.0,
                     110.0,
                     20.0,
                     BLACK,
                 );
+                match &state.sim.chart {
+                    None => {
+                        draw_text(
+                            format!("Seed: {}", state.sim.seed),
+                            50.0,
+                            130.0,
+                            20.0,
+                            BLACK,
+                        );
+                    }
+                    Some(chart) => {
+                        draw_text(format!("Chart: {}", chart.title), 50.0, 130.0, 20.0, BLACK);
+                    }
+                }
                 if root_ui().button(
                     Vec2::from_slice(&[screen_width() / 2.0 - 61.0, screen_height() / 2.0]),
                     "View Leaderboards",
                 ) {
+                    state.leave_versus();
                     state.state = GameState::Leaderboards;
                 }
                 if root_ui().button(
@@ -482,6 +748,446 @@ async fn main() {
                 ) {
                     state.goto_main_menu();
                 }
+                if state.editor.is_some()
+                    && root_ui().button(
+                        Vec2::from_slice(&[
+                            screen_width() / 2.0 - 61.0,
+                            screen_height() / 2.0 + 75.0,
+                        ]),
+                        "Back to editor",
+                    )
+                {
+                    state.return_to_editor();
+                }
+                if root_ui().button(
+                    Vec2::from_slice(&[screen_width() / 2.0 - 61.0, screen_height() / 2.0 + 50.0]),
+                    "Save replay",
+                ) && state.replay_path.is_none()
+                {
+                    state.save_replay();
+                }
+                if let Some(path) = &state.replay_path {
+                    draw_text(
+                        format!("Replay saved to {}", path.display()),
+                        50.0,
+                        230.0,
+                        20.0,
+                        BLACK,
+                    );
+                }
+            }
+            GameState::VersusQueue => {
+                clear_background(GRAY);
+                draw_text("Waiting for an opponent...", 50.0, 50.0, 20.0, BLACK);
+
+                if root_ui().button(
+                    Vec2::from_slice(&[screen_width() / 2.0 - 61.0, screen_height() / 2.0]),
+                    "Cancel",
+                ) {
+                    state.goto_main_menu();
+                } else {
+                    state.poll_versus_queue();
+                }
+            }
+            GameState::SpectateList => {
+                clear_background(GRAY);
+
+                if root_ui().button(Vec2::from_slice(&[50.0, 50.0]), "Refresh") {
+                    if let Err(err) = state.refresh_live_games() {
+                        println!("{:?}", err);
+                    }
+                }
+
+                if state.live_games.is_empty() {
+                    draw_text("Nobody is playing right now", 50.0, 100.0, 20.0, BLACK);
+                }
+
+                let mut selected = None;
+                for (index, game) in state.live_games.iter().enumerate() {
+                    if root_ui().button(
+                        Vec2::from_slice(&[50.0, 100.0 + (index as f32 * 25.0)]),
+                        format!("{} ({} slots)", game.user_name, game.slot_count),
+                    ) {
+                        selected = Some(game.id);
+                    }
+                }
+                if let Some(id) = selected {
+                    if let Err(err) = state.spectate(id) {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GameState::Spectating => {
+                clear_background(GRAY);
+                state.poll_spectating();
+
+                if let Some(spectating) = state.spectating.as_mut() {
+                    let slot_count = spectating.info.slot_count;
+                    let bar_width = TILE_WIDTH * slot_count as f32;
+
+                    hud::draw_lives(spectating.lives, slot_count);
+                    draw_text(
+                        format!("Score: {}", spectating.score),
+                        bar_width,
+                        50.0,
+                        20.0,
+                        BLACK,
+                    );
+                    draw_text(
+                        format!("Watching {}", spectating.info.user_name),
+                        10.0,
+                        20.0,
+                        20.0,
+                        BLACK,
+                    );
+
+                    hud::draw_hit_bar(slot_count);
+                    spectating.update(state.timestep.advance());
+                    for index in 0..slot_count as usize {
+                        if spectating.is_slot_active(index) {
+                            hud::draw_slot_press(index, slot_count);
+                        }
+                    }
+
+                    spectating.tiles.iter().for_each(|tile| tile.draw());
+
+                    if spectating.ended {
+                        draw_text("Game over", 10.0, 40.0, 20.0, BLACK);
+                    }
+                }
+
+                // draw border around game so it looks pretty :)
+                draw_rectangle_lines(0.0, 0.0, screen_width(), screen_height(), 8.0, BLACK);
+            }
+            GameState::ChartList => {
+                clear_background(GRAY);
+
+                if root_ui().button(Vec2::from_slice(&[50.0, 50.0]), "Refresh") {
+                    state.refresh_charts();
+                }
+                if root_ui().button(Vec2::from_slice(&[110.0, 50.0]), "New chart") {
+                    if let Some(dir) = chart_dir() {
+                        state.open_editor(ChartEditor::new_chart(
+                            dir,
+                            state.login_info.user_name.clone(),
+                            SLOT_COUNT.load(Ordering::Relaxed),
+                        ));
+                    }
+                }
+
+                if state.charts.is_empty() {
+                    draw_text(
+                        "No charts found, put chart files in the charts folder",
+                        50.0,
+                        100.0,
+                        20.0,
+                        BLACK,
+                    );
+                }
+
+                let mut selected = None;
+                let mut leader_board = None;
+                let mut edit = None;
+                for (index, (path, chart)) in state.charts.iter().enumerate() {
+                    if root_ui().button(
+                        Vec2::from_slice(&[10.0, 100.0 + (index as f32 * 25.0)]),
+                        "Edit",
+                    ) {
+                        edit = Some(ChartEditor::new(path.clone(), chart.clone()));
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[50.0, 100.0 + (index as f32 * 25.0)]),
+                        "Scores",
+                    ) {
+                        leader_board = Some(chart.leader_board_category());
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[110.0, 100.0 + (index as f32 * 25.0)]),
+                        format!(
+                            "{} - {} ({} slots, {:.0}s)",
+                            chart.title,
+                            chart.author,
+                            chart.slot_count,
+                            chart.length()
+                        ),
+                    ) {
+                        selected = Some(index);
+                    }
+                }
+                if let Some(index) = selected {
+                    let music = match state.charts.get(index) {
+                        None => None,
+                        Some((path, chart)) => {
+                            load_chart_music(path, chart, tick_vol, audio_offset / 1000.0).await
+                        }
+                    };
+                    state.start_chart(index, will_connect, music);
+                }
+                if let Some(category) = leader_board {
+                    if let Err(err) = state.refresh_category_leaderboards(category) {
+                        println!("{:?}", err);
+                    }
+                    state.state = GameState::Leaderboards;
+                }
+                if let Some(editor) = edit {
+                    state.open_editor(editor);
+                }
+            }
+            GameState::PresetList => {
+                clear_background(GRAY);
+
+                if root_ui().button(Vec2::from_slice(&[50.0, 50.0]), "Refresh") {
+                    state.refresh_presets();
+                }
+
+                if state.presets.is_empty() {
+                    draw_text(
+                        "No presets found, put preset files in the presets folder",
+                        50.0,
+                        100.0,
+                        20.0,
+                        BLACK,
+                    );
+                }
+
+                let mut selected = None;
+                let mut leader_board = None;
+                for (index, (_, preset)) in state.presets.iter().enumerate() {
+                    if root_ui().button(
+                        Vec2::from_slice(&[10.0, 100.0 + (index as f32 * 25.0)]),
+                        "Scores",
+                    ) {
+                        leader_board = Some(preset.leader_board_category());
+                    }
+                    if root_ui().button(
+                        Vec2::from_slice(&[70.0, 100.0 + (index as f32 * 25.0)]),
+                        format!("{} ({} lives)", preset.name, preset.lives),
+                    ) {
+                        selected = Some(index);
+                    }
+                }
+                if let Some(index) = selected {
+                    state.start_preset(index, will_connect, parse_seed(&seed_input));
+                }
+                if let Some(category) = leader_board {
+                    if let Err(err) = state.refresh_category_leaderboards(category) {
+                        println!("{:?}", err);
+                    }
+                    state.state = GameState::Leaderboards;
+                }
+            }
+            GameState::PracticeSetup => {
+                clear_background(GRAY);
+                draw_text(
+                    "Practice runs have no lives and are never submitted",
+                    10.0,
+                    30.0,
+                    20.0,
+                    BLACK,
+                );
+                draw_text(
+                    "Press Enter during the run to end it",
+                    10.0,
+                    50.0,
+                    20.0,
+                    BLACK,
+                );
+
+                root_ui().slider(
+                    hash!(),
+                    "Tile speed",
+                    MIN_PRACTICE_SPEED..MAX_PRACTICE_SPEED,
+                    &mut game_settings.practice_speed,
+                );
+                root_ui().slider(
+                    hash!(),
+                    "Seconds between tiles",
+                    MIN_PRACTICE_INTERVAL..MAX_PRACTICE_INTERVAL,
+                    &mut game_settings.practice_interval,
+                );
+                root_ui().slider(
+                    hash!(),
+                    "Slow motion",
+                    MIN_SLOW_MOTION..1.0,
+                    &mut game_settings.slow_motion,
+                );
+
+                if root_ui().button(
+                    Vec2::from_slice(&[screen_width() / 2.0 - 61.0, screen_height() / 2.0]),
+                    "Start practice",
+                ) {
+                    state.start_practice(
+                        game_settings.practice_speed,
+                        game_settings.practice_interval,
+                        game_settings.slow_motion,
+                        parse_seed(&seed_input),
+                    );
+                }
+            }
+            GameState::Editor => {
+                clear_background(GRAY);
+
+                let Some(editor) = state.editor.as_mut() else {
+                    state.goto_main_menu();
+                    request_new_screen_size(400.0, 600.0);
+                    next_frame().await;
+                    continue;
+                };
+                editor.handle_input();
+                editor.draw();
+                match editor.draw_panel() {
+                    None => {}
+                    Some(EditorAction::TogglePreview) => {
+                        if editor.is_previewing() {
+                            editor.stop_preview();
+                        } else {
+                            let sound = match &editor.song {
+                                None => None,
+                                Some(song) => song.sound_from(editor.cursor).await,
+                            };
+                            match sound {
+                                None => {
+                                    editor.status = "Only wav songs can be played".to_string();
+                                }
+                                Some(sound) => editor.start_preview(sound, tick_vol),
+                            }
+                        }
+                    }
+                    Some(EditorAction::TestPlay) => {
+                        editor.stop_preview();
+                        let chart = editor.chart.starting_at(editor.cursor);
+                        let music = match &editor.song {
+                            None => None,
+                            Some(song) => song.sound_from(editor.cursor).await.map(|sound| {
+                                ChartMusic::new(sound, tick_vol, audio_offset / 1000.0)
+                                    .with_song(Rc::clone(song), editor.cursor)
+                            }),
+                        };
+                        state.test_chart(chart, music);
+                    }
+                    Some(EditorAction::Back) => {
+                        editor.stop_preview();
+                        state.goto_main_menu();
+                        request_new_screen_size(400.0, 600.0);
+                    }
+                }
+            }
+            GameState::ReplayList => {
+                clear_background(GRAY);
+
+                if root_ui().button(Vec2::from_slice(&[50.0, 50.0]), "Refresh") {
+                    state.refresh_replays();
+                }
+
+                if state.replays.is_empty() {
+                    draw_text("No replays have been saved", 50.0, 100.0, 20.0, BLACK);
+                }
+
+                let mut selected = None;
+                for (index, (path, replay)) in state.replays.iter().enumerate() {
+                    let y = 100.0 + (index as f32 * 25.0);
+                    let label = format!(
+                        "{} - {} ({})",
+                        replay.user_name,
+                        replay.score,
+                        path.file_stem().unwrap_or_default().to_string_lossy()
+                    );
+                    // replays from an older version would not play out the way the run did, so they are listed but can not be watched
+                    if !replay.is_current() {
+                        draw_text(
+                            format!("{} - old version", label),
+                            50.0,
+                            y + 16.0,
+                            20.0,
+                            DARKGRAY,
+                        );
+                        continue;
+                    }
+                    if root_ui().button(Vec2::from_slice(&[50.0, y]), label) {
+                        selected = Some(index);
+                    }
+                }
+                if let Some(index) = selected {
+                    state.watch_replay(index);
+                }
+            }
+            GameState::WatchingReplay => {
+                clear_background(GRAY);
+                set_sound_volume(TICK_SOUND.get().unwrap(), tick_vol);
+                set_sound_volume(ANTI_TICK_SOUND.get().unwrap(), tick_vol);
+
+                // playback controls
+                if let Some(replay) = state.replay.as_mut() {
+                    if is_key_pressed(KeyCode::Space) {
+                        replay.paused = !replay.paused;
+                    }
+                    if is_key_pressed(KeyCode::Up) {
+                        replay.faster();
+                    }
+                    if is_key_pressed(KeyCode::Down) {
+                        replay.slower();
+                    }
+                    if is_key_pressed(KeyCode::Left) {
+                        replay.seek(replay.sim.tick.saturating_sub(SEEK_TICKS));
+                    }
+                    if is_key_pressed(KeyCode::Right) {
+                        replay.seek(replay.sim.tick + SEEK_TICKS);
+                    }
+                }
+                state.update_replay();
+
+                if let Some(replay) = state.replay.as_ref() {
+                    let slot_count = replay.sim.slot_count;
+                    let bar_width = TILE_WIDTH * slot_count as f32;
+
+                    hud::draw_lives(replay.sim.lives, slot_count);
+                    draw_text(
+                        format!("Score: {}", replay.sim.get_score()),
+                        bar_width,
+                        50.0,
+                        20.0,
+                        BLACK,
+                    );
+                    draw_text(
+                        format!("Replay of {}", replay.replay.user_name),
+                        10.0,
+                        20.0,
+                        20.0,
+                        BLACK,
+                    );
+                    let (played, length) = replay.progress();
+                    draw_text(
+                        format!("{:.1}s / {:.1}s  {}x", played, length, replay.speed()),
+                        10.0,
+                        40.0,
+                        20.0,
+                        BLACK,
+                    );
+                    if replay.paused {
+                        draw_text("Paused", 10.0, 60.0, 20.0, BLACK);
+                    }
+                    draw_text(
+                        "Space: pause, Left/Right: seek, Up/Down: speed",
+                        10.0,
+                        540.0,
+                        16.0,
+                        BLACK,
+                    );
+
+                    hud::draw_hit_bar(slot_count);
+                    for index in 0..slot_count as usize {
+                        if replay.sim.is_slot_active(index) {
+                            hud::draw_slot_press(index, slot_count);
+                        }
+                    }
+
+                    replay.sim.tiles.iter().for_each(|tile| tile.draw());
+                    hud::draw_combo(replay.sim.combo, replay.sim.multiplier(), slot_count);
+                    hud::draw_judgement(state.judgement, replay.sim.tick, slot_count);
+                }
+
+                // draw border around game so it looks pretty :)
+                draw_rectangle_lines(0.0, 0.0, screen_width(), screen_height(), 8.0, BLACK);
             }
             GameState::Leaderboards => {
                 clear_background(GRAY);
@@ -489,6 +1195,26 @@ async fn main() {
                 if root_ui().button(Vec2::from_slice(&[50.0, 50.0]), "Refresh") {
                     let _ = state.refresh_leaderboards();
                 }
+                if root_ui().button(Vec2::from_slice(&[50.0, 75.0]), "Daily") {
+                    let _ = state.refresh_daily_leaderboards();
+                }
+                for (index, mode) in [
+                    Difficulty::Zen,
+                    Difficulty::TimeAttack,
+                    Difficulty::SuddenDeath,
+                ]
+                .into_iter()
+                .enumerate()
+                {
+                    if root_ui().button(
+                        Vec2::from_slice(&[50.0, 100.0 + (index as f32 * 25.0)]),
+                        mode.name(),
+                    ) {
+                        if let Some(category) = mode.leader_board_category() {
+                            let _ = state.refresh_category_leaderboards(category);
+                        }
+                    }
+                }
 
                 match &state.leader_boards {
                     None => {}
@@ -498,39 +1224,33 @@ async fn main() {
                                 screen_width() / 2.0 - 50.0,
                                 (index as f32 * 25.0) + screen_height() / 4.0,
                             ];
-                            draw_text(&format!("{}", entry), pos[0], pos[1], 20.0, BLACK);
+                            draw_text(format!("{}", entry), pos[0], pos[1], 20.0, BLACK);
                         }
                     }
                 }
             }
         }
 
-        // allow the game to be exited to the main menu
-        if is_key_pressed(KeyCode::B) {
-            state.goto_main_menu();
-            request_new_screen_size(400.0, 600.0);
+        // allow the game to be exited to the main menu, or back to the editor when test playing a chart.
+        // the editor has its own back button, since b is typed into its text boxes
+        if is_key_pressed(KeyCode::B) && state.state != GameState::Editor {
+            if state.editor.is_some() {
+                state.return_to_editor();
+            } else {
+                state.goto_main_menu();
+                request_new_screen_size(400.0, 600.0);
+            }
         }
 
         #[cfg(debug_assertions)]
         draw_text(
-            &format!("DEBUG {},{}", mouse_position().0, mouse_position().1),
+            format!("DEBUG {},{}", mouse_position().0, mouse_position().1),
             50.0,
             30.0,
             20.0,
             BLACK,
         );
 
-        frame_delay().await; // wait enough time to limit the fps of the game to 60 fps.
         next_frame().await
     }
 }
-
-/// Delays the frame to be 60 fps
-async fn frame_delay() {
-    let minimum_frame_time = 1. / 60.;
-    let frame_time = get_frame_time();
-    if frame_time < minimum_frame_time {
-        let time_to_sleep = (minimum_frame_time - frame_time) * 1000.;
-        sleep(Duration::from_millis(time_to_sleep as u64));
-    }
-}
@@ -0,0 +1,267 @@
+//! music is a source file containing the playback of the song of a chart, which the simulation is kept in time with
+#![warn(missing_docs)]
+
+use cr_tile_game_common::chart::Chart;
+use cr_tile_game_common::timestep::{ticks_to_secs, MAX_FRAME_TIME, TICKS_PER_SECOND};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use macroquad::audio::{load_sound_from_bytes, play_sound, stop_sound, PlaySoundParams, Sound};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The song of a chart, which starts playing on the tick the chart starts on
+pub struct ChartMusic {
+    /// The song, as it is played from the start of the chart
+    start_sound: Sound,
+    /// The song as it is currently being played, which starts part way through once the run has been paused
+    sound: Sound,
+    /// The volume the song plays at
+    volume: f32,
+    /// The tick of the simulation the song starts playing on
+    start_tick: u64,
+    /// The time the song started playing, once it has. After a pause, this is when it would have started to be where it is now
+    started_at: Option<Instant>,
+    /// The number of seconds the song is heard after it is played, tiles are delayed by this much to line up with what is heard
+    offset: f32,
+    /// True while the song is stopped part way through, the run is kept in time by the clock instead until the song is resumed
+    interrupted: bool,
+    /// The decoded song, only kept for wav songs, which are the only songs that can be picked up again after a pause
+    song: Option<Rc<WavSong>>,
+    /// The time in the decoded song that the start sound starts at, later than 0 when a chart is test played from part way through
+    song_start: f32,
+    /// The song from the point it was paused at, loaded while the run counts down to resuming
+    resume_sound: Option<Sound>,
+}
+
+/// Returns the path of the song of the chart, the audio path of the chart is relative to the chart file
+pub fn chart_audio_path(chart_path: &Path, chart: &Chart) -> Option<PathBuf> {
+    let audio = chart.audio.as_ref()?;
+    Some(chart_path.parent()?.join(audio))
+}
+
+/// Loads the song of the chart
+pub async fn load_chart_music(
+    chart_path: &Path,
+    chart: &Chart,
+    volume: f32,
+    offset: f32,
+) -> Option<ChartMusic> {
+    let audio_path = chart_audio_path(chart_path, chart)?;
+    let bytes = match std::fs::read(&audio_path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            println!("{}: {:?}", audio_path.display(), err);
+            return None;
+        }
+    };
+    // wav songs are kept decoded as well, so they can be picked up again after a pause
+    let is_wav = audio_path.extension().is_some_and(|ext| ext == "wav");
+    let song = match is_wav {
+        true => WavSong::load(&audio_path).ok(),
+        false => None,
+    };
+    match load_sound_from_bytes(&bytes).await {
+        Ok(sound) => {
+            let music = ChartMusic::new(sound, volume, offset);
+            Some(match song {
+                None => music,
+                Some(song) => music.with_song(Rc::new(song), 0.0),
+            })
+        }
+        Err(err) => {
+            println!("{}: {:?}", audio_path.display(), err);
+            None
+        }
+    }
+}
+
+impl ChartMusic {
+    /// Creates a song that starts playing on the first tick
+    pub fn new(sound: Sound, volume: f32, offset: f32) -> Self {
+        Self {
+            start_sound: sound.clone(),
+            sound,
+            volume,
+            start_tick: 0,
+            started_at: None,
+            offset,
+            interrupted: false,
+            song: None,
+            song_start: 0.0,
+            resume_sound: None,
+        }
+    }
+
+    /// Keeps the decoded song the sound was made from, along with the time in it the sound starts at, so the song can be resumed after a pause
+    pub fn with_song(mut self, song: Rc<WavSong>, song_start: f32) -> Self {
+        self.song = Some(song);
+        self.song_start = song_start;
+        self
+    }
+
+    /// Sets the tick the song starts playing on
+    pub fn set_start_tick(&mut self, start_tick: u64) {
+        self.start_tick = start_tick;
+    }
+
+    /// Returns the number of ticks to simulate to catch the simulation up to the song.
+    /// Before the song starts, or while it is stopped, the ticks from the fixed timestep clock are used instead.
+    /// A single frame catches up by no more than the fixed timestep clock would, so a long stall is caught up over several frames
+    pub fn ticks_due(&self, tick: u64, clock_ticks: u32) -> u64 {
+        match self.started_at {
+            None => clock_ticks as u64,
+            Some(_) if self.interrupted => clock_ticks as u64,
+            Some(started_at) => {
+                let position = started_at.elapsed().as_secs_f32() - self.offset;
+                let target =
+                    self.start_tick as i64 + (position * TICKS_PER_SECOND as f32).floor() as i64;
+                let max_ticks = (MAX_FRAME_TIME * TICKS_PER_SECOND as f64).ceil() as i64;
+                (target - tick as i64).clamp(0, max_ticks) as u64
+            }
+        }
+    }
+
+    /// Starts the song if the simulation has reached the tick it starts on
+    pub fn start_if_due(&mut self, tick: u64) {
+        if self.started_at.is_none() && tick >= self.start_tick {
+            self.play(&self.sound.clone());
+            self.started_at = Some(Instant::now());
+        }
+    }
+
+    /// Plays the given sound at the volume of the song
+    fn play(&self, sound: &Sound) {
+        play_sound(
+            sound,
+            PlaySoundParams {
+                looped: false,
+                volume: self.volume,
+            },
+        );
+    }
+
+    /// Returns the number of seconds into the start sound that is heard on the given tick
+    fn position_at(&self, tick: u64) -> f32 {
+        ticks_to_secs(tick.saturating_sub(self.start_tick)) + self.offset
+    }
+
+    /// Stops the song
+    pub fn stop(&self) {
+        stop_sound(&self.sound);
+    }
+
+    /// Stops the song if it has started, until it is resumed
+    pub fn interrupt(&mut self) {
+        if self.started_at.is_some() {
+            self.stop();
+            self.interrupted = true;
+        }
+    }
+
+    /// Loads the song from the point that is heard on the given tick, so it can be resumed from there.
+    /// Only wav songs can be loaded part way through, other songs stay stopped for the rest of the run
+    pub async fn prepare_resume(&mut self, tick: u64) {
+        if !self.interrupted {
+            return;
+        }
+        if let Some(song) = &self.song {
+            let time = self.song_start + self.position_at(tick);
+            self.resume_sound = song.sound_from(time).await;
+        }
+    }
+
+    /// Plays the song again from the given tick, if it was loaded from there when the countdown to resuming started
+    pub fn resume(&mut self, tick: u64) {
+        let Some(sound) = self.resume_sound.take() else {
+            return;
+        };
+        let position = Duration::from_secs_f32(self.position_at(tick).max(0.0));
+        if let Some(started_at) = Instant::now().checked_sub(position) {
+            self.play(&sound);
+            self.sound = sound;
+            self.started_at = Some(started_at);
+            self.interrupted = false;
+        }
+    }
+
+    /// Stops the song and sets it back to before it started, so it can be played from the start again
+    pub fn reset(&mut self) {
+        self.stop();
+        self.sound = self.start_sound.clone();
+        self.started_at = None;
+        self.interrupted = false;
+        self.resume_sound = None;
+    }
+}
+
+/// The samples of a decoded wav file
+enum WavSamples {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+}
+
+/// A decoded wav song, which unlike a `Sound` can be played starting from any point
+pub struct WavSong {
+    /// The format of the song
+    spec: WavSpec,
+    /// Every sample of the song, with the channels interleaved
+    samples: WavSamples,
+}
+
+impl WavSong {
+    /// Decodes the wav file at the given path
+    pub fn load(path: &Path) -> Result<Self, hound::Error> {
+        let reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            SampleFormat::Int => WavSamples::Int(reader.into_samples().collect::<Result<_, _>>()?),
+            SampleFormat::Float => {
+                WavSamples::Float(reader.into_samples().collect::<Result<_, _>>()?)
+            }
+        };
+        Ok(Self { spec, samples })
+    }
+
+    /// Returns the number of samples in the song, across every channel
+    fn sample_count(&self) -> usize {
+        match &self.samples {
+            WavSamples::Int(samples) => samples.len(),
+            WavSamples::Float(samples) => samples.len(),
+        }
+    }
+
+    /// Returns the length of the song in seconds
+    pub fn length(&self) -> f32 {
+        self.sample_count() as f32 / self.spec.channels as f32 / self.spec.sample_rate as f32
+    }
+
+    /// Returns the song starting at the given time in seconds, as the bytes of a wav file
+    pub fn slice_from(&self, time: f32) -> Result<Vec<u8>, hound::Error> {
+        let frame = (time.max(0.0) * self.spec.sample_rate as f32) as usize;
+        let start = (frame * self.spec.channels as usize).min(self.sample_count());
+
+        let mut bytes = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut bytes, self.spec)?;
+        match &self.samples {
+            WavSamples::Int(samples) => {
+                for sample in &samples[start..] {
+                    writer.write_sample(*sample)?;
+                }
+            }
+            WavSamples::Float(samples) => {
+                for sample in &samples[start..] {
+                    writer.write_sample(*sample)?;
+                }
+            }
+        }
+        writer.finalize()?;
+        Ok(bytes.into_inner())
+    }
+
+    /// Loads the song starting at the given time in seconds, so it can be played
+    pub async fn sound_from(&self, time: f32) -> Option<Sound> {
+        let bytes = self.slice_from(time).ok()?;
+        load_sound_from_bytes(&bytes).await.ok()
+    }
+}
@@ -0,0 +1,65 @@
+//! pause is a source file containing the state of a paused run, and the countdown it resumes with
+#![warn(missing_docs)]
+
+use macroquad::prelude::*;
+use std::time::Instant;
+
+/// The number of seconds counted down before a paused run starts again
+pub const RESUME_COUNTDOWN: f32 = 3.0;
+
+/// A run that is not being simulated, either waiting on the player or counting down to resume
+#[derive(Debug, Clone, Copy)]
+pub enum PauseState {
+    /// The run is paused until the player resumes it
+    Paused,
+    /// The run is counting down to resume, since the given time
+    Resuming(Instant),
+}
+
+impl PauseState {
+    /// Returns the seconds left before the run resumes, or none while it is waiting on the player
+    pub fn countdown(&self) -> Option<f32> {
+        match self {
+            PauseState::Paused => None,
+            PauseState::Resuming(started_at) => {
+                Some((RESUME_COUNTDOWN - started_at.elapsed().as_secs_f32()).max(0.0))
+            }
+        }
+    }
+
+    /// Returns true once the countdown has run out and the run should be simulated again
+    pub fn is_finished(&self) -> bool {
+        self.countdown() == Some(0.0)
+    }
+
+    /// Draws a shade over the run, with the countdown if the run is resuming
+    pub fn draw(&self) {
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Color::new(0.0, 0.0, 0.0, 0.5),
+        );
+        match self.countdown() {
+            None => {
+                draw_text(
+                    "Paused",
+                    (screen_width() / 2.0) - 45.0,
+                    (screen_height() / 2.0) - 40.0,
+                    40.0,
+                    WHITE,
+                );
+            }
+            Some(countdown) => {
+                draw_text(
+                    format!("{}", countdown.ceil() as u32),
+                    (screen_width() / 2.0) - 15.0,
+                    screen_height() / 2.0,
+                    80.0,
+                    WHITE,
+                );
+            }
+        }
+    }
+}
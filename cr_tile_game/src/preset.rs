@@ -0,0 +1,83 @@
+//! preset is a source file containing the loading of difficulty presets from the presets directory
+#![warn(missing_docs)]
+
+use crate::user_files::{list_json_files, user_dir};
+use cr_tile_game_common::daily::Difficulty;
+use cr_tile_game_common::preset::DifficultyPreset;
+use std::fs;
+use std::path::PathBuf;
+
+/// Returns the directory difficulty presets are loaded from, next to the settings of the game
+pub fn preset_dir() -> Option<PathBuf> {
+    user_dir("presets")
+}
+
+/// Creates the preset directory with a copy of every built in preset, the first time presets are read.
+/// Normal and hard mode are played with these copies, so changing them tunes those modes
+fn seed_preset_dir() {
+    let Some(dir) = preset_dir() else {
+        return;
+    };
+    if dir.exists() {
+        return;
+    }
+    if let Err(err) = fs::create_dir_all(&dir) {
+        println!("{}: {:?}", dir.display(), err);
+        return;
+    }
+    for (file_name, json) in DifficultyPreset::built_in() {
+        if let Err(err) = fs::write(dir.join(file_name), json) {
+            println!("{}: {:?}", file_name, err);
+        }
+    }
+}
+
+/// Returns the preset the given mode is played with, loaded from its file in the preset directory.
+/// The copy built into the game is used if the file is missing or can not be loaded
+pub fn mode_preset(difficulty: Difficulty) -> DifficultyPreset {
+    seed_preset_dir();
+    let file_name = difficulty.preset_file_name();
+    let Some(path) = preset_dir().map(|dir| dir.join(file_name)) else {
+        return difficulty.preset();
+    };
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(err) => {
+            println!("{}: {:?}", path.display(), err);
+            return difficulty.preset();
+        }
+    };
+    match DifficultyPreset::from_json(&json) {
+        Ok(preset) => preset,
+        Err(err) => {
+            println!("{}: {:?}", path.display(), err);
+            difficulty.preset()
+        }
+    }
+}
+
+/// Loads every valid preset in the preset directory, presets that can not be loaded are skipped with an error printed.
+/// The files of the built in modes are left out, since they are played from the main menu
+pub fn list_presets() -> Vec<(PathBuf, DifficultyPreset)> {
+    seed_preset_dir();
+    let built_in = DifficultyPreset::built_in().map(|(file_name, _)| file_name);
+    list_json_files(preset_dir())
+        .into_iter()
+        .filter(|path| {
+            !path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| built_in.contains(&name))
+        })
+        .filter_map(|path| {
+            let json = fs::read_to_string(&path).ok()?;
+            match DifficultyPreset::from_json(&json) {
+                Ok(preset) => Some((path, preset)),
+                Err(err) => {
+                    println!("{}: {:?}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,150 @@
+//! replay is a source file containing the saving, loading and playback of recorded runs
+#![warn(missing_docs)]
+
+use crate::user_files::{list_json_files, load_json, user_dir};
+use cr_tile_game_common::replay::Replay;
+use cr_tile_game_common::simulation::{SimEvent, Simulation};
+use cr_tile_game_common::timestep::{ticks_to_secs, TICKS_PER_SECOND};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The speeds a replay can be played back at, as a multiple of real time
+pub const PLAYBACK_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+/// The index in `PLAYBACK_SPEEDS` that playback starts at
+const DEFAULT_SPEED_INDEX: usize = 2;
+/// The number of ticks a single seek moves the replay by
+pub const SEEK_TICKS: u64 = 5 * TICKS_PER_SECOND as u64;
+
+/// Returns the directory replays are saved in, next to the settings of the game
+pub fn replay_dir() -> Option<PathBuf> {
+    user_dir("replays")
+}
+
+/// Saves the replay to a new file in the replay directory, and returns the path of the file
+pub fn save_replay(replay: &Replay) -> io::Result<PathBuf> {
+    let dir = replay_dir().ok_or(io::ErrorKind::NotFound)?;
+    fs::create_dir_all(&dir)?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("replay_{}.json", secs));
+    fs::write(&path, serde_json::to_string(replay)?)?;
+    Ok(path)
+}
+
+/// Loads the replay saved at the given path
+pub fn load_replay(path: &Path) -> Option<Replay> {
+    load_json(path)
+}
+
+/// Loads every replay in the replay directory, newest first
+pub fn list_replays() -> Vec<(PathBuf, Replay)> {
+    list_json_files(replay_dir())
+        .into_iter()
+        .filter_map(|path| load_replay(&path).map(|replay| (path, replay)))
+        .collect()
+}
+
+/// Plays a replay back by feeding its inputs into a fresh simulation
+pub struct ReplayPlayer {
+    /// The replay being played
+    pub replay: Replay,
+    /// The simulation the inputs are fed into
+    pub sim: Simulation,
+    /// The index of the next input that has not been fed into the simulation yet
+    next_input: usize,
+    /// True if playback is paused
+    pub paused: bool,
+    /// The index in `PLAYBACK_SPEEDS` of the current playback speed
+    speed_index: usize,
+    /// Ticks that have been earned by the playback speed but not simulated yet
+    tick_budget: f32,
+}
+
+impl ReplayPlayer {
+    /// Creates a player at the start of the replay
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            sim: replay.new_simulation(),
+            replay,
+            next_input: 0,
+            paused: false,
+            speed_index: DEFAULT_SPEED_INDEX,
+            tick_budget: 0.0,
+        }
+    }
+
+    /// Returns the current playback speed
+    pub fn speed(&self) -> f32 {
+        PLAYBACK_SPEEDS[self.speed_index]
+    }
+
+    /// Speeds playback up to the next speed
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+
+    /// Slows playback down to the previous speed
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    /// Returns true once the whole replay has been played
+    pub fn is_finished(&self) -> bool {
+        self.sim.tick >= self.replay.ticks
+    }
+
+    /// Returns the seconds that have been played, and the length of the replay in seconds
+    pub fn progress(&self) -> (f32, f32) {
+        (
+            ticks_to_secs(self.sim.tick),
+            ticks_to_secs(self.replay.ticks),
+        )
+    }
+
+    /// Feeds the inputs made on the current tick into the simulation, then steps it once
+    fn step(&mut self) {
+        while let Some(input) = self.replay.inputs.get(self.next_input) {
+            if input.tick > self.sim.tick {
+                break;
+            }
+            self.sim.apply_input(input);
+            self.next_input += 1;
+        }
+        self.sim.step();
+    }
+
+    /// Advances playback by the given number of real time ticks scaled by the playback speed, and returns what happened
+    pub fn update(&mut self, ticks: u32) -> Vec<SimEvent> {
+        if self.paused {
+            return vec![];
+        }
+        self.tick_budget += ticks as f32 * self.speed();
+        while self.tick_budget >= 1.0 && !self.is_finished() {
+            self.step();
+            self.tick_budget -= 1.0;
+        }
+        if self.is_finished() {
+            self.tick_budget = 0.0;
+        }
+        self.sim.drain_events()
+    }
+
+    /// Moves playback to the given tick, the simulation is played again from the start when seeking backwards
+    pub fn seek(&mut self, tick: u64) {
+        let tick = tick.min(self.replay.ticks);
+        if tick < self.sim.tick {
+            self.sim = self.replay.new_simulation();
+            self.next_input = 0;
+        }
+        while self.sim.tick < tick {
+            self.step();
+        }
+        self.tick_budget = 0.0;
+        // nothing that happened while seeking should be heard
+        self.sim.drain_events();
+    }
+}
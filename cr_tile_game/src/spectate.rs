@@ -0,0 +1,91 @@
+//! spectate is a source file containing the state of a live game being watched by a spectator
+#![warn(missing_docs)]
+
+use cr_tile_game_common::simulation::HIT_LENGTH;
+use cr_tile_game_common::spectate::{LiveEvent, LiveGameInfo};
+use cr_tile_game_common::tile::Tile;
+use cr_tile_game_common::timestep::secs_to_ticks;
+
+/// A read-only copy of another players game, rebuilt from the events they stream to the server
+pub struct SpectateState {
+    /// The game being watched
+    pub info: LiveGameInfo,
+    /// Every tile currently on the screen of the player
+    pub tiles: Vec<Tile>,
+    /// The score of the player
+    pub score: i32,
+    /// The lives of the player
+    pub lives: i32,
+    /// The tick each slot was last pressed on by the player
+    pub slot_press_tick: Vec<Option<u64>>,
+    /// The number of ticks simulated since spectating started
+    pub tick: u64,
+    /// True once the player has finished their game
+    pub ended: bool,
+}
+
+impl SpectateState {
+    /// Creates an empty view of the given game, the server sends the tiles already on the field right after
+    pub fn new(info: LiveGameInfo) -> Self {
+        Self {
+            slot_press_tick: vec![None; info.slot_count as usize],
+            tick: 0,
+            info,
+            tiles: vec![],
+            score: 0,
+            lives: 0,
+            ended: false,
+        }
+    }
+
+    /// Applies a single event from the game
+    pub fn apply_event(&mut self, event: LiveEvent) {
+        match event {
+            LiveEvent::TileSpawned {
+                id,
+                slot,
+                speed,
+                kind,
+                ticks,
+            } => {
+                let mut tile = Tile::new(id, slot, speed).with_kind(kind);
+                // tiles already on the field when spectating started are moved to where they are now
+                for _ in 0..ticks {
+                    tile.tick();
+                }
+                self.tiles.push(tile);
+            }
+            LiveEvent::TileHit { id } | LiveEvent::TileMissed { id } => {
+                self.tiles.retain(|tile| tile.id != id);
+            }
+            LiveEvent::SlotPressed { slot } => {
+                if let Some(press_tick) = self.slot_press_tick.get_mut(slot as usize) {
+                    *press_tick = Some(self.tick);
+                }
+            }
+            LiveEvent::Status { score, lives, .. } => {
+                self.score = score;
+                self.lives = lives;
+            }
+        }
+    }
+
+    /// Returns true if the player pressed the slot recently enough that it should be drawn as pressed
+    pub fn is_slot_active(&self, index: usize) -> bool {
+        match self.slot_press_tick.get(index) {
+            Some(Some(press_tick)) => self.tick - press_tick < secs_to_ticks(HIT_LENGTH),
+            _ => false,
+        }
+    }
+
+    /// Moves every tile by the given number of ticks, the player sends a miss event for tiles that leave the screen so they are not removed here
+    pub fn update(&mut self, ticks: u32) {
+        if self.ended {
+            return;
+        }
+        for _ in 0..ticks {
+            self.tiles.iter_mut().for_each(|tile| tile.tick());
+            self.tick += 1;
+        }
+    }
+}
@@ -1,91 +1,131 @@
-use crate::{get_color, HIT_DISTANCE, MIDDLE_BAR, SLOT_COUNT};
+use crate::get_color;
+pub use cr_tile_game_common::tile::TILE_HEIGHT;
+use cr_tile_game_common::tile::{Tile, TileKind};
 use macroquad::prelude::*;
 use macroquad::shapes::draw_rectangle_lines;
-use std::sync::atomic::Ordering;
 
 /// The width of the tile for spacing purposes
 pub const TILE_WIDTH: f32 = 100.0;
-/// The height of the tile, for rendering and spacing, as well as duration that the tile can be hit
-pub const TILE_HEIGHT: f32 = 100.0;
 /// The gap between each tile slot
 pub const TILE_MARGIN: f32 = 10.0;
 
-#[derive(Debug, Clone)]
-/// A struct representing the tiles within the game.
-pub struct Tile {
-    /// The distance of the tile to the bottom of the screen (the y coordinate)
-    pub distance: f32,
-    /// The slot that the tile is in
-    pub slot: u8,
-    /// The speed of the tile in units per frame.
-    pub speed: f32,
+/// Drawing for the tiles of the simulation, which knows nothing about rendering itself
+pub trait DrawTile {
+    /// Draws the tile on the screen.
+    fn draw(&self);
 }
 
-impl Tile {
-    /// Generates a new random tile.
-    pub fn random_new(speed: f32) -> Self {
-        Self {
-            distance: -TILE_HEIGHT,
-            slot: rand::gen_range(0, SLOT_COUNT.load(Ordering::Relaxed)),
-            speed,
-        }
-    }
-
-    /// Ticks the movement of the tile
-    pub fn tick(&mut self) {
-        self.distance += self.speed;
-    }
-
-    /// Returns true if the tile is overlapping the bar
-    pub fn is_hit(&self, slot: u8) -> bool {
-        if slot != self.slot {
-            return false;
-        }
-
-        let dist = {
-            let middle_y = self.distance + (TILE_HEIGHT / 2.0); // middle of the tile in terms of its y coordinate
-            (middle_y - MIDDLE_BAR).abs()
-        }; // distance from the bar in terms of absolute value
-        dist <= ((TILE_HEIGHT + HIT_DISTANCE) / 2.0) // if the distance to the bar is <= the size of the bar plus the size of the tile
-    }
-
-    /// Draws the tile on the screen.
-    pub fn draw(&self) {
+impl DrawTile for Tile {
+    fn draw(&self) {
         #[cfg(debug_assertions)]
         {
             let x = self.slot as f32 * TILE_WIDTH;
             let y = self.distance;
-            draw_text(&format!("{},{}", x, y), x, y, 24.0, BLACK);
+            draw_text(format!("{},{}", x, y), x, y, 24.0, BLACK);
         }
-        let rect_x = (self.slot as f32 * TILE_WIDTH) + (TILE_MARGIN / 2.0);
         let width = TILE_WIDTH - TILE_MARGIN;
+        let is_hit = self.is_hit(self.slot) || self.held.is_some();
+        // hold tiles reach up from their head by the length of their tail
+        let top = self.distance - self.length;
+        let height = TILE_HEIGHT + self.length;
+        let slots = self.slots();
+
+        // chord tiles are joined across every slot they cover, so it is clear they are hit together
+        if let (Some(first), Some(last)) = (slots.first(), slots.last()) {
+            if first != last {
+                let y = self.distance + (TILE_HEIGHT / 2.0);
+                draw_line(
+                    (*first as f32 + 0.5) * TILE_WIDTH,
+                    y,
+                    (*last as f32 + 0.5) * TILE_WIDTH,
+                    y,
+                    6.0,
+                    DARKGRAY,
+                );
+            }
+        }
+
+        for slot in slots {
+            let rect_x = (slot as f32 * TILE_WIDTH) + (TILE_MARGIN / 2.0);
+            let color = match self.kind {
+                TileKind::Bomb => BLACK,
+                TileKind::Bonus => GREEN,
+                _ => get_color(slot as usize),
+            };
 
-        let color = get_color(self.slot as usize);
-        let is_hit = self.is_hit(self.slot);
+            draw_rectangle(rect_x, top, width, height, {
+                // held tiles are filled in more, so it is clear the hold is still going
+                match self.held {
+                    Some(_) => Color::new(color.r, color.g, color.b, 0.8),
+                    None => Color::new(color.r, color.g, color.b, 0.5),
+                }
+            });
 
-        draw_rectangle(rect_x, self.distance, width, TILE_HEIGHT, {
-            Color::new(color.r, color.g, color.b, 0.5)
-        });
+            draw_rectangle_lines(
+                rect_x,
+                top,
+                width,
+                height,
+                {
+                    // make thickness react to when the tile is hit
+                    match is_hit {
+                        true => 6.0,
+                        false => 4.0,
+                    }
+                },
+                {
+                    // make color react when tile is hit, bombs are always outlined in red
+                    match (self.kind, is_hit) {
+                        (TileKind::Bomb, _) => RED,
+                        (_, true) => BLACK,
+                        (_, false) => DARKGRAY,
+                    }
+                },
+            );
 
-        draw_rectangle_lines(
-            rect_x,
-            self.distance,
-            width,
-            TILE_HEIGHT,
-            {
-                // make thickness react to when the tile is hit
-                match is_hit {
-                    true => 6.0,
-                    false => 4.0,
+            // bombs are crossed out, and bonus tiles have a plus for the life they give back
+            let center_x = rect_x + (width / 2.0);
+            let center_y = self.distance + (TILE_HEIGHT / 2.0);
+            let size = TILE_HEIGHT / 4.0;
+            match self.kind {
+                TileKind::Bomb => {
+                    draw_line(
+                        center_x - size,
+                        center_y - size,
+                        center_x + size,
+                        center_y + size,
+                        4.0,
+                        RED,
+                    );
+                    draw_line(
+                        center_x - size,
+                        center_y + size,
+                        center_x + size,
+                        center_y - size,
+                        4.0,
+                        RED,
+                    );
                 }
-            },
-            {
-                // make color react when tile is hit
-                match is_hit {
-                    true => BLACK,
-                    false => DARKGRAY,
+                TileKind::Bonus => {
+                    draw_line(
+                        center_x - size,
+                        center_y,
+                        center_x + size,
+                        center_y,
+                        4.0,
+                        WHITE,
+                    );
+                    draw_line(
+                        center_x,
+                        center_y - size,
+                        center_x,
+                        center_y + size,
+                        4.0,
+                        WHITE,
+                    );
                 }
-            },
-        );
+                _ => {}
+            }
+        }
     }
 }
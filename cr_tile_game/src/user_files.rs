@@ -0,0 +1,30 @@
+//! user_files is a source file containing helpers for the files the game keeps in the users home directory
+#![warn(missing_docs)]
+
+use cr_program_settings::get_user_home;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the directory with the given name, inside the directory the settings of the game are saved in
+pub fn user_dir(name: &str) -> Option<PathBuf> {
+    get_user_home().map(|home| home.join(env!("CARGO_CRATE_NAME")).join(name))
+}
+
+/// Returns every json file in the directory, sorted by name with the last name first
+pub fn list_json_files(dir: Option<PathBuf>) -> Vec<PathBuf> {
+    let Some(entries) = dir.and_then(|dir| fs::read_dir(dir).ok()) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort_by(|a, b| b.cmp(a));
+    paths
+}
+
+/// Loads the json file at the given path
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
@@ -0,0 +1,70 @@
+//! versus is a source file containing the state of a head-to-head match against another player
+#![warn(missing_docs)]
+
+use cr_tile_game_common::packet::VersusStatus;
+use macroquad::prelude::{draw_text, BLACK, DARKGREEN, RED};
+use std::time::{Duration, Instant};
+
+/// How often the player sends their status to the server during a match
+pub const VERSUS_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+/// The state of a versus match the player is in
+pub struct VersusState {
+    /// The name of the opponent
+    pub opponent: String,
+    /// The latest status of the opponent that the server sent us
+    pub opponent_status: VersusStatus,
+    /// None while the match is still being played, otherwise true if the player won
+    pub result: Option<bool>,
+    /// The last time the status of the player was sent to the server
+    pub last_update: Instant,
+}
+
+impl VersusState {
+    /// Creates the state for a match against the given opponent
+    pub fn new(opponent: String, lives: i32) -> Self {
+        Self {
+            opponent,
+            opponent_status: VersusStatus { score: 0, lives },
+            result: None,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Draws the live score and lives of the opponent
+    pub fn draw_opponent(&self, x: f32, y: f32) {
+        draw_text(format!("VS {}", self.opponent), x, y, 16.0, BLACK);
+        draw_text(
+            format!("Score: {}", self.opponent_status.score),
+            x,
+            y + 15.0,
+            16.0,
+            BLACK,
+        );
+        draw_text(
+            format!("Lives: {}", self.opponent_status.lives.max(0)),
+            x,
+            y + 30.0,
+            16.0,
+            BLACK,
+        );
+    }
+
+    /// Draws the result of the match on the score screen
+    pub fn draw_result(&self, x: f32, y: f32) {
+        let (text, color) = match self.result {
+            None => ("Waiting for the match result...".to_string(), BLACK),
+            Some(true) => (format!("You won against {}!", self.opponent), DARKGREEN),
+            Some(false) => (format!("You lost against {}", self.opponent), RED),
+        };
+        draw_text(text, x, y, 20.0, color);
+        draw_text(
+            format!("Opponent score: {}", self.opponent_status.score),
+            x,
+            y + 20.0,
+            20.0,
+            BLACK,
+        );
+    }
+}
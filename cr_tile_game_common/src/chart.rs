@@ -0,0 +1,228 @@
+use crate::tile::{TileKind, MIDDLE_BAR, TILE_HEIGHT};
+use crate::timestep::TICKS_PER_SECOND;
+use serde::{Deserialize, Serialize};
+
+/// The most slots a chart can use, one for each key bind
+pub const MAX_SLOT_COUNT: u8 = 5;
+
+/// A single tile authored in a chart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartNote {
+    /// The time in seconds from the start of the chart that the tile reaches the middle of the hit bar
+    pub time: f32,
+    /// The slot the tile falls in, starting from 0
+    pub slot: u8,
+    /// The type of tile, normal if it is left out
+    #[serde(default)]
+    pub kind: TileKind,
+    /// The speed of the tile in units per simulation tick
+    pub speed: f32,
+}
+
+impl ChartNote {
+    /// Returns the number of seconds the note has to be held for, 0 for notes that are not holds
+    pub fn duration(&self) -> f32 {
+        match self.kind {
+            TileKind::Hold { duration } => duration,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns true if the tile of the note covers the given slot, which chords do for every slot they are in
+    pub fn covers(&self, slot: u8) -> bool {
+        match self.kind {
+            TileKind::Chord { slots } => slots & (1 << slot) != 0,
+            _ => self.slot == slot,
+        }
+    }
+}
+
+/// A fixed sequence of tiles loaded from a json file, played instead of randomly spawned tiles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chart {
+    /// The name of the chart, shown when picking a chart
+    pub title: String,
+    /// Who made the chart, or the song it is for
+    #[serde(default)]
+    pub author: String,
+    /// The number of slots the chart is played with
+    pub slot_count: u8,
+    /// The path of the song the chart is played to, relative to the chart file. Wav and ogg files are supported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+    /// The tempo of the song in beats per minute, used by the editor to place tiles on the beat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f32>,
+    /// Every tile in the chart, they do not need to be in order
+    pub notes: Vec<ChartNote>,
+}
+
+#[derive(Debug)]
+pub enum ChartError {
+    /// The file is not a valid chart json file
+    ParseError(serde_json::Error),
+    /// The slot count of the chart is 0 or more than the game supports
+    InvalidSlotCount(u8),
+    /// The note at the given index is in a slot the chart does not have
+    SlotOutOfRange(usize),
+    /// The note at the given index has a time, speed or hold duration that is negative, zero or not a number,
+    /// or is a chord that covers less than two slots or does not cover its own slot
+    InvalidNote(usize),
+}
+
+impl Chart {
+    /// Parses a chart from json, and checks that every note can be played
+    pub fn from_json(json: &str) -> Result<Self, ChartError> {
+        let chart: Chart = serde_json::from_str(json).map_err(ChartError::ParseError)?;
+        chart.validate()?;
+        Ok(chart)
+    }
+
+    /// Returns the chart as json, in the same format it is loaded from
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Checks that every note of the chart can be played
+    pub fn validate(&self) -> Result<(), ChartError> {
+        if self.slot_count == 0 || self.slot_count > MAX_SLOT_COUNT {
+            return Err(ChartError::InvalidSlotCount(self.slot_count));
+        }
+        for (index, note) in self.notes.iter().enumerate() {
+            if note.slot >= self.slot_count {
+                return Err(ChartError::SlotOutOfRange(index));
+            }
+            if !(note.time >= 0.0
+                && note.time.is_finite()
+                && note.speed > 0.0
+                && note.speed.is_finite())
+            {
+                return Err(ChartError::InvalidNote(index));
+            }
+            match note.kind {
+                TileKind::Hold { duration } if !(duration > 0.0 && duration.is_finite()) => {
+                    return Err(ChartError::InvalidNote(index));
+                }
+                TileKind::Chord { slots } => {
+                    if slots >> self.slot_count != 0 {
+                        return Err(ChartError::SlotOutOfRange(index));
+                    }
+                    if slots.count_ones() < 2 || slots & (1 << note.slot) == 0 {
+                        return Err(ChartError::InvalidNote(index));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a hash of the tiles of the chart, which stays the same when the title or author change
+    pub fn hash(&self) -> u64 {
+        fnv_hash(&serde_json::to_string(&(self.slot_count, &self.notes)).unwrap())
+    }
+
+    /// Returns the leaderboard category scores of this chart are ranked in
+    pub fn leader_board_category(&self) -> String {
+        format!("chart_{:016x}", self.hash())
+    }
+
+    /// Returns a copy of the chart that starts at the given time, notes before it are removed and every other note is moved earlier to match
+    pub fn starting_at(&self, time: f32) -> Chart {
+        Chart {
+            notes: self
+                .notes
+                .iter()
+                .filter(|note| note.time >= time)
+                .map(|note| ChartNote {
+                    time: note.time - time,
+                    ..note.clone()
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the length of the chart in seconds, up to the time the last note reaches the hit bar, or the end of the last hold
+    pub fn length(&self) -> f32 {
+        self.notes
+            .iter()
+            .map(|note| note.time + note.duration())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Hashes the content with fnv-1a, so the hash is the same on every platform and every version of rust
+pub(crate) fn fnv_hash(content: &str) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    content.bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The notes of a chart, turned into the ticks the tiles spawn on
+#[derive(Debug, Clone)]
+pub struct ChartSchedule {
+    /// Every note along with the tick it spawns on, sorted by spawn tick
+    spawns: Vec<(u64, ChartNote)>,
+    /// The index of the next note to spawn
+    next: usize,
+    /// The tick the chart starts on, tiles that have to spawn before their note time push the start of the chart back
+    pub start_tick: u64,
+}
+
+/// Returns the number of ticks a tile with the given speed takes from spawning to reaching the middle of the hit bar
+pub fn travel_ticks(speed: f32) -> f32 {
+    (MIDDLE_BAR + (TILE_HEIGHT / 2.0)) / speed
+}
+
+impl ChartSchedule {
+    /// Works out the spawn tick of every note of the chart
+    pub fn new(chart: &Chart) -> Self {
+        // the tick of each spawn, before the start of the chart is pushed back
+        let relative: Vec<f32> = chart
+            .notes
+            .iter()
+            .map(|note| note.time * TICKS_PER_SECOND as f32 - travel_ticks(note.speed))
+            .collect();
+        let start_tick = relative
+            .iter()
+            .fold(0.0, |earliest: f32, tick| earliest.min(*tick))
+            .abs()
+            .ceil() as u64;
+
+        let mut spawns: Vec<(u64, ChartNote)> = chart
+            .notes
+            .iter()
+            .zip(relative)
+            .map(|(note, tick)| ((tick + start_tick as f32).round() as u64, note.clone()))
+            .collect();
+        spawns.sort_by_key(|(tick, _)| *tick);
+
+        Self {
+            spawns,
+            next: 0,
+            start_tick,
+        }
+    }
+
+    /// Returns every note that should spawn on or before the given tick, that has not been returned yet
+    pub fn due(&mut self, tick: u64) -> Vec<ChartNote> {
+        let mut due = vec![];
+        while let Some((spawn_tick, note)) = self.spawns.get(self.next) {
+            if *spawn_tick > tick {
+                break;
+            }
+            due.push(note.clone());
+            self.next += 1;
+        }
+        due
+    }
+
+    /// Returns true once every note has spawned
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.spawns.len()
+    }
+}
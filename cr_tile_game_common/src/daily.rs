@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Normal,
+    Hard,
+    /// A preset loaded from a file, the simulation keeps the preset itself
+    Custom,
+    /// An endless run with no lives, that lasts until the player ends it
+    Zen,
+    /// A run that lasts a set time with no lives, scored by the number of tiles hit
+    TimeAttack,
+    /// A run that ends the moment a tile is missed or a key press hits nothing
+    SuddenDeath,
+    /// A run with a fixed tile speed and spawn interval and no lives, that is never submitted
+    Practice,
+}
+
+impl Difficulty {
+    /// Returns the name of the mode, as it is shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Custom => "Custom",
+            Difficulty::Zen => "Zen",
+            Difficulty::TimeAttack => "Time Attack",
+            Difficulty::SuddenDeath => "Sudden Death",
+            Difficulty::Practice => "Practice",
+        }
+    }
+
+    /// Returns true if missing a tile costs a life, zen, time attack and practice runs have no lives
+    pub fn has_lives(&self) -> bool {
+        !matches!(
+            self,
+            Difficulty::Zen | Difficulty::TimeAttack | Difficulty::Practice
+        )
+    }
+
+    /// Returns true if runs of this mode are submitted to a leaderboard, practice runs never are
+    pub fn is_ranked(&self) -> bool {
+        *self != Difficulty::Practice
+    }
+
+    /// Returns the leaderboard category runs of this mode are ranked in, or none for the modes ranked on the main leaderboard.
+    /// Runs of custom presets are ranked by their preset instead, and practice runs are not ranked at all
+    pub fn leader_board_category(&self) -> Option<String> {
+        match self {
+            Difficulty::Normal | Difficulty::Hard | Difficulty::Custom | Difficulty::Practice => {
+                None
+            }
+            Difficulty::Zen => Some("mode_zen".to_string()),
+            Difficulty::TimeAttack => Some("mode_time_attack".to_string()),
+            Difficulty::SuddenDeath => Some("mode_sudden_death".to_string()),
+        }
+    }
+}
+
+/// The challenge every player gets for a single day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyChallenge {
+    /// The number of days since the unix epoch, in utc
+    pub day: u64,
+    /// The seed every player uses for their tile sequence
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub slot_count: u8,
+}
+
+impl DailyChallenge {
+    /// Creates the challenge for the given day, every call with the same day creates the same challenge
+    pub fn for_day(day: u64) -> Self {
+        // splitmix64, so neighboring days get very different seeds
+        let mut seed = day.wrapping_add(0x9E3779B97F4A7C15);
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94D049BB133111EB);
+        seed ^= seed >> 31;
+
+        Self {
+            day,
+            seed,
+            difficulty: match seed % 2 {
+                0 => Difficulty::Normal,
+                _ => Difficulty::Hard,
+            },
+            slot_count: 3 + (seed % 3) as u8,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// The udp port that services listen on for discovery requests
+pub const DISCOVERY_PORT: u16 = 8115;
+
+/// Packets sent over udp when looking for services on the local network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DiscoveryPacket {
+    /// Broadcast by a client that is looking for services
+    Request,
+    /// Sent back to the client by every service that heard the request
+    Response(DiscoveryResponse),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryResponse {
+    /// The name the service advertises itself with
+    pub name: String,
+    /// The tcp port that the service accepts players on
+    pub port: u16,
+}
@@ -0,0 +1,135 @@
+use crate::tile::{HIT_DISTANCE, TILE_HEIGHT};
+use serde::{Deserialize, Serialize};
+
+/// How close to the middle of the hit bar a tile was when it was hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Judgement {
+    Perfect,
+    Great,
+    Good,
+    /// The tile went off the bottom of the field without being hit
+    Miss,
+}
+
+impl Judgement {
+    /// Every judgement, from best to worst
+    pub const ALL: [Judgement; 4] = [
+        Judgement::Perfect,
+        Judgement::Great,
+        Judgement::Good,
+        Judgement::Miss,
+    ];
+
+    /// Returns the name of the judgement, as it is shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            Judgement::Perfect => "Perfect",
+            Judgement::Great => "Great",
+            Judgement::Good => "Good",
+            Judgement::Miss => "Miss",
+        }
+    }
+}
+
+/// The furthest the middle of a tile can be from the middle of the hit bar for each judgement
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct JudgementWindows {
+    pub perfect: f32,
+    pub great: f32,
+    /// Tiles further than this from the bar can not be hit
+    pub good: f32,
+}
+
+impl Default for JudgementWindows {
+    fn default() -> Self {
+        Self {
+            perfect: 15.0,
+            great: 35.0,
+            // any overlap between the tile and the bar is a hit
+            good: (TILE_HEIGHT + HIT_DISTANCE) / 2.0,
+        }
+    }
+}
+
+impl JudgementWindows {
+    /// Returns the judgement a tile the given distance from the middle of the bar gets, or none if it is too far to be hit
+    pub fn judge(&self, distance: f32) -> Option<Judgement> {
+        if distance <= self.perfect {
+            Some(Judgement::Perfect)
+        } else if distance <= self.great {
+            Some(Judgement::Great)
+        } else if distance <= self.good {
+            Some(Judgement::Good)
+        } else {
+            None
+        }
+    }
+}
+
+/// The points each judgement adds to the score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JudgementScores {
+    pub perfect: i32,
+    pub great: i32,
+    pub good: i32,
+    pub miss: i32,
+}
+
+impl Default for JudgementScores {
+    fn default() -> Self {
+        Self {
+            perfect: 3,
+            great: 2,
+            good: 1,
+            miss: 0,
+        }
+    }
+}
+
+impl JudgementScores {
+    /// Returns the points the judgement is worth
+    pub fn points(&self, judgement: Judgement) -> i32 {
+        match judgement {
+            Judgement::Perfect => self.perfect,
+            Judgement::Great => self.great,
+            Judgement::Good => self.good,
+            Judgement::Miss => self.miss,
+        }
+    }
+}
+
+/// The number of times each judgement was given during a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JudgementCounts {
+    pub perfect: u32,
+    pub great: u32,
+    pub good: u32,
+    pub miss: u32,
+}
+
+impl JudgementCounts {
+    /// Counts the judgement
+    pub fn add(&mut self, judgement: Judgement) {
+        match judgement {
+            Judgement::Perfect => self.perfect += 1,
+            Judgement::Great => self.great += 1,
+            Judgement::Good => self.good += 1,
+            Judgement::Miss => self.miss += 1,
+        }
+    }
+
+    /// Returns the number of tiles that were hit, which is every judgement but misses
+    pub fn hits(&self) -> u32 {
+        self.perfect + self.great + self.good
+    }
+
+    /// Returns the number of times the judgement was given
+    pub fn get(&self, judgement: Judgement) -> u32 {
+        match judgement {
+            Judgement::Perfect => self.perfect,
+            Judgement::Great => self.great,
+            Judgement::Good => self.good,
+            Judgement::Miss => self.miss,
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::fmt::{Display, Formatter};
 
+/// The way scores are counted, raised whenever scoring changes so that older scores can not be ranked against newer ones.
+/// Scores from before it was added counted the tiles hit rather than judgement points, and are version 0
+pub const SCORE_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LeaderBoardEntry {
     username: String,
     discriminator: String,
     score: i32,
+    /// The way the score was counted, entries stored before scores had a version are 0
+    #[serde(default)]
+    score_version: u32,
 }
 
 impl Display for LeaderBoardEntry {
@@ -14,7 +22,11 @@ impl Display for LeaderBoardEntry {
             f,
             "{}#{}: {}",
             self.username, self.discriminator, self.score
-        )
+        )?;
+        if !self.is_current() {
+            write!(f, " (old scoring)")?;
+        }
+        Ok(())
     }
 }
 
@@ -24,11 +36,22 @@ impl LeaderBoardEntry {
             username,
             discriminator,
             score,
+            score_version: SCORE_VERSION,
         }
     }
     pub fn get_score(&self) -> i32 {
         self.score
     }
+
+    /// Returns true if the score was counted the way scores are counted now
+    pub fn is_current(&self) -> bool {
+        self.score_version == SCORE_VERSION
+    }
+
+    /// Returns true if this entry should replace the given best entry of the same player, a best counted the old way is always replaced
+    pub fn beats(&self, best: &LeaderBoardEntry) -> bool {
+        !best.is_current() || self.score > best.score
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,8 +67,37 @@ impl LeaderBoardList {
         &self.list
     }
 
+    /// Sorts the list from the highest score down, scores counted the old way are ranked below every current score
     pub fn sort_list(&mut self) {
         self.list
-            .sort_by(|item1, item2| item2.score.cmp(&item1.score));
+            .sort_by_key(|item| Reverse((item.is_current(), item.score)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_stored_before_score_versions_are_old() {
+        let old: LeaderBoardEntry = serde_json::from_str(
+            r#"{ "username": "Old", "discriminator": "abcdef", "score": 500 }"#,
+        )
+        .unwrap();
+        assert!(!old.is_current());
+        assert_eq!(old.to_string(), "Old#abcdef: 500 (old scoring)");
+
+        let new = LeaderBoardEntry::new("New".to_string(), 10, "123456".to_string());
+        assert!(new.is_current());
+        assert!(new.beats(&old));
+        assert!(!new.beats(&LeaderBoardEntry::new(
+            "New".to_string(),
+            20,
+            "123456".to_string()
+        )));
+
+        let mut list = LeaderBoardList::new(vec![old, new]);
+        list.sort_list();
+        assert_eq!(list.get_list()[0].get_score(), 10);
     }
 }
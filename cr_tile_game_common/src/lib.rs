@@ -1,2 +1,12 @@
+pub mod chart;
+pub mod daily;
+pub mod discovery;
+pub mod judgement;
 pub mod leader_board_stat;
 pub mod packet;
+pub mod preset;
+pub mod replay;
+pub mod simulation;
+pub mod spectate;
+pub mod tile;
+pub mod timestep;
@@ -1,4 +1,7 @@
+use crate::daily::DailyChallenge;
 use crate::leader_board_stat::LeaderBoardList;
+use crate::spectate::{LiveEvent, LiveGameInfo};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::{Display, Formatter};
@@ -8,12 +11,156 @@ use std::hash::{Hash, Hasher};
 pub enum ClientPacket {
     SubmitDataPacket(GameDataPacket),
     GetLeaderBoardsList,
+    /// Places the player in the versus queue, the server replies with `VersusMatchFound` once an opponent joins
+    JoinVersus(VersusJoin),
+    /// Removes the player from the versus queue, or forfeits the match they are in
+    LeaveVersus,
+    /// The current status of the player during a versus match, relayed to their opponent
+    VersusUpdate(VersusStatus),
+    /// Announces that the player started a game that others can spectate
+    StartLiveGame {
+        user_name: String,
+        slot_count: u8,
+    },
+    /// Events that happened in the live game of the player, relayed to every spectator
+    LiveEvents(Vec<LiveEvent>),
+    /// The live game of the player is over
+    EndLiveGame,
+    /// Requests every game that can currently be spectated, the server replies with `LiveGames`
+    GetLiveGames,
+    /// Starts spectating the live game with the given id, the server replies with `SpectateStarted`
+    Spectate(u64),
+    /// Stops spectating the current game
+    StopSpectating,
+    /// Requests the challenge for the current day, the server replies with `DailyChallenge`
+    GetDailyChallenge,
+    /// Starts an attempt at the daily challenge, which uses up the ranked attempt of the day even if the run is never finished.
+    /// The server replies with `DailyAttemptStarted`
+    StartDailyAttempt(DailyAttemptPacket),
+    /// Submits a score for the daily challenge, only the first attempt of each day is ranked
+    SubmitDailyScore(DailyScorePacket),
+    /// Requests the leaderboard of the current daily challenge
+    GetDailyLeaderBoard,
+    /// Submits a score to a leaderboard other than the main one, such as the leaderboard of a chart
+    SubmitCategoryScore(CategoryScorePacket),
+    /// Requests the leaderboard of the given category, the server replies with `LeaderBoard`
+    GetCategoryLeaderBoard(String),
+}
+
+impl ClientPacket {
+    /// Returns true if the server packet is the reply to this packet, an error is the reply to any packet that expects one.
+    /// Packets the server does not reply to never have a reply
+    pub fn is_reply(&self, reply: &ServerPacket) -> bool {
+        match self {
+            ClientPacket::JoinVersus(_)
+            | ClientPacket::LeaveVersus
+            | ClientPacket::VersusUpdate(_)
+            | ClientPacket::StartLiveGame { .. }
+            | ClientPacket::LiveEvents(_)
+            | ClientPacket::EndLiveGame
+            | ClientPacket::StopSpectating => false,
+            _ if matches!(reply, ServerPacket::ErrorState) => true,
+            ClientPacket::SubmitDataPacket(_)
+            | ClientPacket::GetLeaderBoardsList
+            | ClientPacket::GetDailyLeaderBoard
+            | ClientPacket::SubmitCategoryScore(_)
+            | ClientPacket::GetCategoryLeaderBoard(_) => {
+                matches!(reply, ServerPacket::LeaderBoard(_))
+            }
+            ClientPacket::SubmitDailyScore(_) => matches!(
+                reply,
+                ServerPacket::LeaderBoard(_) | ServerPacket::DailyAttemptUsed(_)
+            ),
+            ClientPacket::GetLiveGames => matches!(reply, ServerPacket::LiveGames(_)),
+            ClientPacket::Spectate(_) => matches!(reply, ServerPacket::SpectateStarted(_)),
+            ClientPacket::GetDailyChallenge => matches!(reply, ServerPacket::DailyChallenge(_)),
+            ClientPacket::StartDailyAttempt(_) => {
+                matches!(reply, ServerPacket::DailyAttemptStarted(_))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerPacket {
     LeaderBoard(LeaderBoardList),
     ErrorState,
+    /// The player has been matched with an opponent
+    VersusMatchFound(VersusMatch),
+    /// The latest status of the players opponent
+    VersusOpponentUpdate(VersusStatus),
+    /// The versus match is over, and the player either won or lost
+    VersusGameOver {
+        won: bool,
+    },
+    /// Every game that can currently be spectated
+    LiveGames(Vec<LiveGameInfo>),
+    /// The player is now spectating the given game
+    SpectateStarted(LiveGameInfo),
+    /// Events from the game being spectated
+    LiveEvents(Vec<LiveEvent>),
+    /// The game being spectated is over
+    LiveGameEnded,
+    /// The challenge for the current day
+    DailyChallenge(DailyChallenge),
+    /// Reply to a daily score from a player that already used their ranked attempt for that day
+    DailyAttemptUsed(LeaderBoardList),
+    /// Reply to starting a daily challenge attempt, true if it is the ranked attempt of the day
+    DailyAttemptStarted(bool),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersusJoin {
+    pub login_info: LoginInfo,
+    /// The slot count the player would like to play with
+    pub slot_count: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersusMatch {
+    /// The seed both players use for their tile sequence
+    pub seed: u64,
+    /// The slot count both players use, the slot count of whoever joined the queue first
+    pub slot_count: u8,
+    /// The name of the opponent
+    pub opponent: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct VersusStatus {
+    pub score: i32,
+    pub lives: i32,
+}
+
+/// Deserializes every complete packet at the front of the buffer and removes them from it.
+/// Packets that have only been partially received are left in the buffer so the rest can be appended to them.
+pub fn drain_packets<T: DeserializeOwned>(buf: &mut Vec<u8>) -> Vec<T> {
+    let mut packets = vec![];
+    let mut stream = serde_json::Deserializer::from_slice(buf).into_iter::<T>();
+    let mut consumed = 0;
+
+    loop {
+        match stream.next() {
+            None => {
+                consumed = stream.byte_offset();
+                break;
+            }
+            Some(Ok(packet)) => {
+                packets.push(packet);
+                consumed = stream.byte_offset();
+            }
+            Some(Err(err)) => {
+                if !err.is_eof() {
+                    // the data can never become a valid packet, so throw all of it away
+                    consumed = buf.len();
+                }
+                break;
+            }
+        }
+    }
+
+    buf.drain(0..consumed);
+    packets
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -22,6 +169,27 @@ pub struct GameDataPacket {
     pub login_info: LoginInfo,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyScorePacket {
+    /// The day of the challenge that was played
+    pub day: u64,
+    pub data: GameDataPacket,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyAttemptPacket {
+    /// The day of the challenge being played
+    pub day: u64,
+    pub login_info: LoginInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryScorePacket {
+    /// The leaderboard the score belongs to, e.g. `chart_` followed by the hash of the chart
+    pub category: String,
+    pub data: GameDataPacket,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoginInfo {
     pub user_name: String,
@@ -51,3 +219,38 @@ impl Display for GameDataPacket {
         write!(f, "{}", self.score)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_expected_packets_are_replies() {
+        let request = ClientPacket::GetLiveGames;
+        assert!(request.is_reply(&ServerPacket::LiveGames(vec![])));
+        assert!(request.is_reply(&ServerPacket::ErrorState));
+        assert!(!request.is_reply(&ServerPacket::LiveEvents(vec![])));
+        assert!(!request.is_reply(&ServerPacket::LeaderBoard(LeaderBoardList::new(vec![]))));
+        assert!(!ClientPacket::EndLiveGame.is_reply(&ServerPacket::ErrorState));
+    }
+
+    #[test]
+    fn packets_split_across_reads_are_kept_until_complete() {
+        let json = format!(
+            "{}{}",
+            serde_json::to_string(&ServerPacket::LiveGameEnded).unwrap(),
+            serde_json::to_string(&ServerPacket::VersusGameOver { won: true }).unwrap()
+        );
+        let split = json.len() - 4;
+        let mut buf = json.as_bytes()[..split].to_vec();
+        let packets = drain_packets::<ServerPacket>(&mut buf);
+        assert!(matches!(packets[..], [ServerPacket::LiveGameEnded]));
+        buf.extend_from_slice(&json.as_bytes()[split..]);
+        let packets = drain_packets::<ServerPacket>(&mut buf);
+        assert!(matches!(
+            packets[..],
+            [ServerPacket::VersusGameOver { won: true }]
+        ));
+        assert!(buf.is_empty());
+    }
+}
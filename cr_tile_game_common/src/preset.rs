@@ -0,0 +1,231 @@
+use crate::chart::fnv_hash;
+use crate::daily::Difficulty;
+use crate::judgement::{JudgementScores, JudgementWindows};
+use crate::simulation::HIT_LENGTH;
+use serde::{Deserialize, Serialize};
+
+/// The time between random tiles spawning, from the given tile hit count on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpawnStep {
+    /// The tile hit count the step starts at, each hit adds 2
+    pub hits: i32,
+    /// The number of seconds between tiles spawning
+    pub interval: f32,
+}
+
+/// The speeds random tiles are picked from, from the given tile hit count on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedStep {
+    /// The tile hit count the step starts at, each hit adds 2
+    pub hits: i32,
+    /// The speeds a tile can fall at, one is picked at random for every tile
+    pub speeds: Vec<f32>,
+}
+
+/// The rules random tiles are spawned with, loaded from a json file so modes can be added and tuned without recompiling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyPreset {
+    /// The name of the preset, shown when picking a preset
+    pub name: String,
+    /// The number of lives the player starts with, the run ends when a tile is missed with none left
+    pub lives: i32,
+    /// The tile hit count the run starts at, which starts the spawn and speed curves further along
+    #[serde(default)]
+    pub start_hits: i32,
+    /// The number of seconds a slot counts as pressed for after its key is hit, every slot of a chord has to be pressed within this long
+    #[serde(default = "default_hit_length")]
+    pub hit_length: f32,
+    /// How the time between tiles spawning goes down as more tiles are hit, they do not need to be in order
+    pub spawn_intervals: Vec<SpawnStep>,
+    /// How the speeds of tiles go up as more tiles are hit, they do not need to be in order
+    pub speeds: Vec<SpeedStep>,
+    /// How close to the bar a tile has to be for each judgement
+    #[serde(default)]
+    pub windows: JudgementWindows,
+    /// The points each judgement is worth
+    #[serde(default)]
+    pub scores: JudgementScores,
+}
+
+/// The preset normal mode ships with, kept as json so it can be tuned without changing any code
+const NORMAL_PRESET_JSON: &str = include_str!("../presets/normal.json");
+/// The preset hard mode ships with
+const HARD_PRESET_JSON: &str = include_str!("../presets/hard.json");
+/// The name of the file the normal preset is shipped as
+const NORMAL_PRESET_FILE: &str = "normal.json";
+/// The name of the file the hard preset is shipped as
+const HARD_PRESET_FILE: &str = "hard.json";
+
+/// Returns the hit length presets use when they leave it out
+fn default_hit_length() -> f32 {
+    HIT_LENGTH
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    /// The file is not a valid preset json file
+    ParseError(serde_json::Error),
+    /// The preset starts with less than 0 lives
+    InvalidLives(i32),
+    /// The hit length is negative, zero or not a number
+    InvalidHitLength,
+    /// There are no spawn intervals, or one of them is negative, zero or not a number
+    InvalidSpawnIntervals,
+    /// There are no speed steps, or one of them has no speeds or a speed that is negative, zero or not a number
+    InvalidSpeeds,
+    /// The judgement windows are not numbers, or are not in order from perfect to good
+    InvalidWindows,
+}
+
+impl DifficultyPreset {
+    /// The preset of normal mode, loaded from the preset file it ships with.
+    /// The client plays the copy in its preset directory instead, this one is only used when that copy can not be loaded
+    pub fn normal() -> Self {
+        Self::from_json(NORMAL_PRESET_JSON).expect("the normal preset file is invalid")
+    }
+
+    /// The preset of hard mode, loaded from the preset file it ships with and only used the same way as the normal one.
+    /// It starts further along the curves with fewer lives and a shorter press, and mixes tile speeds
+    pub fn hard() -> Self {
+        Self::from_json(HARD_PRESET_JSON).expect("the hard preset file is invalid")
+    }
+
+    /// Returns the presets that ship with the game, and the file name each is shipped as
+    pub fn built_in() -> [(&'static str, &'static str); 2] {
+        [
+            (NORMAL_PRESET_FILE, NORMAL_PRESET_JSON),
+            (HARD_PRESET_FILE, HARD_PRESET_JSON),
+        ]
+    }
+
+    /// The preset of a practice run, where every tile falls at the given speed and spawns the given number of seconds after the last
+    pub fn practice(speed: f32, interval: f32) -> Self {
+        Self {
+            name: "Practice".to_string(),
+            lives: 0,
+            start_hits: 0,
+            hit_length: HIT_LENGTH,
+            spawn_intervals: vec![SpawnStep { hits: 0, interval }],
+            speeds: vec![SpeedStep {
+                hits: 0,
+                speeds: vec![speed],
+            }],
+            windows: JudgementWindows::default(),
+            scores: JudgementScores::default(),
+        }
+    }
+
+    /// Parses a preset from json, and checks that it can be played
+    pub fn from_json(json: &str) -> Result<Self, PresetError> {
+        let preset: DifficultyPreset =
+            serde_json::from_str(json).map_err(PresetError::ParseError)?;
+        preset.validate()?;
+        Ok(preset)
+    }
+
+    /// Returns the preset as json, in the same format it is loaded from
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Checks that the preset can be played
+    pub fn validate(&self) -> Result<(), PresetError> {
+        let is_positive = |value: f32| value > 0.0 && value.is_finite();
+        if self.lives < 0 {
+            return Err(PresetError::InvalidLives(self.lives));
+        }
+        if !is_positive(self.hit_length) {
+            return Err(PresetError::InvalidHitLength);
+        }
+        if self.spawn_intervals.is_empty()
+            || !self
+                .spawn_intervals
+                .iter()
+                .all(|step| is_positive(step.interval))
+        {
+            return Err(PresetError::InvalidSpawnIntervals);
+        }
+        if self.speeds.is_empty()
+            || !self.speeds.iter().all(|step| {
+                !step.speeds.is_empty() && step.speeds.iter().all(|speed| is_positive(*speed))
+            })
+        {
+            return Err(PresetError::InvalidSpeeds);
+        }
+        let windows = self.windows;
+        if !(is_positive(windows.perfect)
+            && windows.perfect <= windows.great
+            && windows.great <= windows.good
+            && windows.good.is_finite())
+        {
+            return Err(PresetError::InvalidWindows);
+        }
+        Ok(())
+    }
+
+    /// Returns the seconds between tiles spawning at the given tile hit count.
+    /// This is the interval of the latest step the hit count has reached, or of the first step if it has reached none
+    pub fn spawn_interval(&self, hits: i32) -> f32 {
+        self.spawn_intervals
+            .iter()
+            .filter(|step| step.hits <= hits)
+            .max_by_key(|step| step.hits)
+            .or_else(|| self.spawn_intervals.iter().min_by_key(|step| step.hits))
+            .map_or(1.0, |step| step.interval)
+    }
+
+    /// Returns the speeds tiles are picked from at the given tile hit count, found the same way as the spawn interval
+    pub fn speeds_at(&self, hits: i32) -> &[f32] {
+        self.speeds
+            .iter()
+            .filter(|step| step.hits <= hits)
+            .max_by_key(|step| step.hits)
+            .or_else(|| self.speeds.iter().min_by_key(|step| step.hits))
+            .map_or(&[], |step| step.speeds.as_slice())
+    }
+
+    /// Returns a hash of the rules of the preset, which stays the same when the name changes
+    pub fn hash(&self) -> u64 {
+        fnv_hash(
+            &serde_json::to_string(&DifficultyPreset {
+                name: String::new(),
+                ..self.clone()
+            })
+            .unwrap(),
+        )
+    }
+
+    /// Returns the leaderboard category scores of this preset are ranked in
+    pub fn leader_board_category(&self) -> String {
+        format!("preset_{:016x}", self.hash())
+    }
+}
+
+impl Difficulty {
+    /// Returns the preset of the built in difficulty, the other modes are played with the normal preset.
+    /// Custom and practice runs keep their own preset so they get the normal preset
+    pub fn preset(&self) -> DifficultyPreset {
+        match self {
+            Difficulty::Hard => DifficultyPreset::hard(),
+            Difficulty::Normal
+            | Difficulty::Custom
+            | Difficulty::Zen
+            | Difficulty::TimeAttack
+            | Difficulty::SuddenDeath
+            | Difficulty::Practice => DifficultyPreset::normal(),
+        }
+    }
+
+    /// Returns the name of the file the preset of the mode is shipped as, found the same way as the preset
+    pub fn preset_file_name(&self) -> &'static str {
+        match self {
+            Difficulty::Hard => HARD_PRESET_FILE,
+            Difficulty::Normal
+            | Difficulty::Custom
+            | Difficulty::Zen
+            | Difficulty::TimeAttack
+            | Difficulty::SuddenDeath
+            | Difficulty::Practice => NORMAL_PRESET_FILE,
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::chart::Chart;
+use crate::daily::Difficulty;
+use crate::judgement::{JudgementScores, JudgementWindows};
+use crate::preset::DifficultyPreset;
+use crate::simulation::Simulation;
+use serde::{Deserialize, Serialize};
+
+/// The version of the simulation that replays are recorded with.
+/// Raised whenever the simulation changes in a way that makes older replays play out differently, such as how presses are judged
+pub const REPLAY_VERSION: u32 = 2;
+
+/// Whether an input pressed or released a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InputKind {
+    #[default]
+    Press,
+    Release,
+}
+
+/// A single key press or release made during a run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReplayInput {
+    /// The tick of the simulation the key was pressed or released on
+    pub tick: u64,
+    /// The slot the key belongs to
+    pub slot: u8,
+    /// Whether the key was pressed or released, replays from before keys could be held only have presses
+    #[serde(default)]
+    pub kind: InputKind,
+    /// How far into the tick a press was made, as a fraction of a tick, so it is judged at the moment it was made
+    #[serde(default)]
+    pub offset: f32,
+}
+
+/// Everything needed to play a run again exactly as it happened, the simulation is rebuilt from the seed and fed the same inputs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// The version of the simulation the run was played with, 0 for replays saved before replays had a version
+    #[serde(default)]
+    pub version: u32,
+    /// The name of the player who played the run
+    pub user_name: String,
+    pub difficulty: Difficulty,
+    pub slot_count: u8,
+    /// The seed the rng of the run was created from
+    pub seed: u64,
+    /// The final score of the run, so it can be shown without playing the replay
+    pub score: i32,
+    /// The number of ticks the run lasted for
+    pub ticks: u64,
+    /// Every key press made during the run, in the order they were made
+    pub inputs: Vec<ReplayInput>,
+    /// The chart that was played, if the run was of a chart
+    #[serde(default)]
+    pub chart: Option<Chart>,
+    /// The preset the run was played with, if it was played with a preset loaded from a file, a tuned copy of a built in preset, or was a practice run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<DifficultyPreset>,
+    /// The judgement windows the run was played with
+    #[serde(default)]
+    pub windows: JudgementWindows,
+    /// The points each judgement was worth in the run
+    #[serde(default)]
+    pub scores: JudgementScores,
+}
+
+impl Replay {
+    /// Creates a replay of the run the simulation has played so far
+    pub fn from_simulation(sim: &Simulation, user_name: String) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            user_name,
+            difficulty: sim.difficulty,
+            slot_count: sim.slot_count,
+            seed: sim.seed,
+            score: sim.get_score(),
+            ticks: sim.tick,
+            inputs: sim.inputs.clone(),
+            chart: sim.chart.clone(),
+            preset: (matches!(sim.difficulty, Difficulty::Custom | Difficulty::Practice)
+                || (sim.chart.is_none() && sim.preset != sim.difficulty.preset()))
+            .then(|| sim.preset.clone()),
+            windows: sim.windows,
+            scores: sim.scores,
+        }
+    }
+
+    /// Returns true if the replay was recorded with the current simulation, older replays would not play out the way the run did
+    pub fn is_current(&self) -> bool {
+        self.version == REPLAY_VERSION
+    }
+
+    /// Creates the simulation the run started with
+    pub fn new_simulation(&self) -> Simulation {
+        let mut sim = match (&self.chart, &self.preset) {
+            (Some(chart), _) => Simulation::from_chart(chart.clone()),
+            (None, Some(preset)) => {
+                Simulation::with_preset(self.difficulty, preset.clone(), self.slot_count, self.seed)
+            }
+            (None, None) => Simulation::new(self.difficulty, self.slot_count, self.seed),
+        };
+        sim.windows = self.windows;
+        sim.scores = self.scores;
+        sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays the replay into a new simulation the way the replay viewer does, feeding each input in before the tick it was made on
+    fn play(replay: &Replay) -> Simulation {
+        let mut sim = replay.new_simulation();
+        let mut inputs = replay.inputs.iter().peekable();
+        while sim.tick < replay.ticks {
+            while let Some(input) = inputs.next_if(|input| input.tick <= sim.tick) {
+                sim.apply_input(input);
+            }
+            sim.step();
+        }
+        sim
+    }
+
+    #[test]
+    fn a_replay_plays_out_the_same_as_the_run() {
+        let mut sim = Simulation::new(Difficulty::Hard, 4, 7);
+        for tick in 0..3600 {
+            // press tiles at different points in the tick, so each is judged differently than it would be on the tick itself
+            let offset = (tick % 4) as f32 / 4.0;
+            let near = sim
+                .tiles
+                .iter()
+                .find(|tile| tile.distance_from_bar_after(offset) < 40.0)
+                .map(|tile| tile.slot);
+            if let Some(slot) = near {
+                sim.press_slot_at(slot, offset);
+                sim.release_slot(slot);
+            }
+            if tick % 97 == 0 {
+                sim.press_slot_at(0, offset);
+            }
+            sim.step();
+            if sim.is_over() {
+                break;
+            }
+        }
+        let events = sim.drain_events();
+        assert!(events.iter().any(|event| event.judgement().is_some()));
+        assert!(sim.inputs.iter().any(|input| input.offset > 0.0));
+
+        let json =
+            serde_json::to_string(&Replay::from_simulation(&sim, "Test".to_string())).unwrap();
+        let replay: Replay = serde_json::from_str(&json).unwrap();
+        assert!(replay.is_current());
+        let mut played = play(&replay);
+
+        assert_eq!(played.tick, sim.tick);
+        assert_eq!(played.get_score(), sim.get_score());
+        assert_eq!(played.judgements, sim.judgements);
+        assert_eq!(played.max_combo, sim.max_combo);
+        assert_eq!(played.lives, sim.lives);
+        assert_eq!(played.tiles, sim.tiles);
+        assert_eq!(played.inputs, sim.inputs);
+        assert_eq!(played.drain_events(), events);
+    }
+
+    #[test]
+    fn replays_saved_without_a_version_are_old() {
+        let json = r#"{
+            "user_name": "Test",
+            "difficulty": "Normal",
+            "slot_count": 3,
+            "seed": 7,
+            "score": 0,
+            "ticks": 0,
+            "inputs": []
+        }"#;
+        let replay: Replay = serde_json::from_str(json).unwrap();
+        assert_eq!(replay.version, 0);
+        assert!(!replay.is_current());
+    }
+}
@@ -0,0 +1,923 @@
+use crate::chart::{Chart, ChartSchedule};
+use crate::daily::Difficulty;
+use crate::judgement::{Judgement, JudgementCounts, JudgementScores, JudgementWindows};
+use crate::preset::DifficultyPreset;
+use crate::replay::{InputKind, ReplayInput};
+use crate::spectate::LiveEvent;
+use crate::tile::{Tile, TileKind};
+use crate::timestep::{secs_to_ticks, ticks_to_secs, TICKS_PER_SECOND};
+use rand::prelude::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The number of seconds a key press counts as pressed for, the slots of a chord have to be pressed within this long of each other
+pub const HIT_LENGTH: f32 = 0.125;
+/// The number of seconds a time attack run lasts
+pub const TIME_ATTACK_SECONDS: u64 = 60;
+/// The combo needed for each score multiplier, from the highest multiplier down
+pub const COMBO_MULTIPLIERS: [(u32, i32); 4] = [(50, 4), (25, 3), (10, 2), (0, 1)];
+
+/// Something that happened during a tick of the simulation, for the frontend to react to with sound, drawing or networking
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimEvent {
+    /// A tile was spawned at the top of the field
+    TileSpawned {
+        id: u32,
+        slot: u8,
+        speed: f32,
+        kind: TileKind,
+    },
+    /// The player pressed the key for the given slot
+    SlotPressed { slot: u8 },
+    /// The player hit the tile with the given id, and how close to the middle of the bar it was.
+    /// For hold tiles this is when the hold is finished, and the judgement is the one its head was hit with
+    TileHit { id: u32, judgement: Judgement },
+    /// The player hit the head of the hold tile with the given id, and has to hold it until its tail reaches the bar.
+    /// Nothing is scored until the hold is finished or let go of
+    HoldStarted { id: u32, judgement: Judgement },
+    /// The player let go of the hold tile with the given id too early, and only got points for the part that was held
+    HoldBroken { id: u32 },
+    /// The player let go of the key for the given slot
+    SlotReleased { slot: u8 },
+    /// The tile with the given id went off the bottom of the field
+    TileMissed { id: u32 },
+    /// The player hit the bomb tile with the given id
+    BombHit { id: u32 },
+    /// The bomb or bonus tile with the given id went off the bottom of the field, which costs nothing
+    TilePassed { id: u32 },
+    /// The player lost a life, and has the given number of lives left
+    LifeLost { lives: i32 },
+    /// The player got a life back, and has the given number of lives left
+    LifeGained { lives: i32 },
+}
+
+impl SimEvent {
+    /// Returns the event spectators need to rebuild the game, if they need this one
+    pub fn to_live_event(&self) -> Option<LiveEvent> {
+        match *self {
+            SimEvent::TileSpawned {
+                id,
+                slot,
+                speed,
+                kind,
+            } => Some(LiveEvent::TileSpawned {
+                id,
+                slot,
+                speed,
+                kind,
+                ticks: 0,
+            }),
+            SimEvent::SlotPressed { slot } => Some(LiveEvent::SlotPressed { slot }),
+            SimEvent::TileHit { id, .. }
+            | SimEvent::HoldBroken { id }
+            | SimEvent::BombHit { id } => Some(LiveEvent::TileHit { id }),
+            SimEvent::TileMissed { id } | SimEvent::TilePassed { id } => {
+                Some(LiveEvent::TileMissed { id })
+            }
+            SimEvent::LifeLost { .. }
+            | SimEvent::LifeGained { .. }
+            | SimEvent::HoldStarted { .. }
+            | SimEvent::SlotReleased { .. } => None,
+        }
+    }
+
+    /// Returns the judgement the event gave the player, if it gave one
+    pub fn judgement(&self) -> Option<Judgement> {
+        match *self {
+            SimEvent::TileHit { judgement, .. } | SimEvent::HoldStarted { judgement, .. } => {
+                Some(judgement)
+            }
+            SimEvent::TileMissed { .. } | SimEvent::HoldBroken { .. } => Some(Judgement::Miss),
+            _ => None,
+        }
+    }
+}
+
+/// The rules of a single run, with no rendering or sound, so it can be run anywhere the same way
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    /// The difficulty the run is played on
+    pub difficulty: Difficulty,
+    /// The rules random tiles are spawned with
+    pub preset: DifficultyPreset,
+    /// The number of slots tiles can spawn in
+    pub slot_count: u8,
+    /// The seed the rng of the run was created from, showing it lets a run be played again
+    pub seed: u64,
+    /// The rng that every gameplay decision is made with, so a run can be reproduced from its seed
+    rng: ChaCha8Rng,
+    /// A vector containing all the tiles that are in the game
+    pub tiles: Vec<Tile>,
+    /// The tick each slot was last pressed on
+    pub slot_press_tick: Vec<Option<u64>>,
+    /// True for each slot while its key is held down
+    pub slot_held: Vec<bool>,
+    /// The number of ticks that have been simulated
+    pub tick: u64,
+    /// The tick the last tile was spawned on
+    pub last_tile_tick: u64,
+    /// The number of seconds delayed between spawning tiles
+    pub tile_spawn_time: f32,
+    /// Goes up by 2 for every tile the player hits, the more tiles are hit the faster tiles spawn
+    pub tile_hit_count: i32,
+    /// How close to the bar a tile has to be for each judgement
+    pub windows: JudgementWindows,
+    /// The points each judgement is worth
+    pub scores: JudgementScores,
+    /// The number of times each judgement has been given
+    pub judgements: JudgementCounts,
+    /// The points earned from judgements, the score is these points less the number of key presses
+    pub points: i32,
+    /// The number of tiles hit in a row, without missing a tile or pressing a key that hits nothing
+    pub combo: u32,
+    /// The highest combo reached during the run
+    pub max_combo: u32,
+    /// The number of lives the player has
+    pub lives: i32,
+    /// The number of lives the player starts with, bonus tiles can not give back more than this
+    pub max_lives: i32,
+    /// The number of times the player has hit a slot key
+    pub slot_clicks: i32,
+    /// The id given to the next tile that is spawned
+    pub next_tile_id: u32,
+    /// Every key press made so far, so the run can be saved as a replay
+    pub inputs: Vec<ReplayInput>,
+    /// The chart being played, tiles are spawned randomly when there is no chart
+    pub chart: Option<Chart>,
+    /// The spawn tick of every note of the chart being played
+    schedule: Option<ChartSchedule>,
+    /// Events that have happened since they were last drained
+    events: Vec<SimEvent>,
+    /// True once the run has been ended by the player, or by a mistake in sudden death
+    ended: bool,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new(Difficulty::Normal, 3, 0)
+    }
+}
+
+impl Simulation {
+    /// Creates a new run, every run with the same arguments plays out the same given the same inputs
+    pub fn new(difficulty: Difficulty, slot_count: u8, seed: u64) -> Self {
+        Self::with_preset(difficulty, difficulty.preset(), slot_count, seed)
+    }
+
+    /// Creates a new run played with a preset loaded from a file
+    pub fn from_preset(preset: DifficultyPreset, slot_count: u8, seed: u64) -> Self {
+        Self::with_preset(Difficulty::Custom, preset, slot_count, seed)
+    }
+
+    /// Creates a new practice run, where every tile falls at the given speed and spawns the given number of seconds after the last
+    pub fn practice(speed: f32, interval: f32, slot_count: u8, seed: u64) -> Self {
+        Self::with_preset(
+            Difficulty::Practice,
+            DifficultyPreset::practice(speed, interval),
+            slot_count,
+            seed,
+        )
+    }
+
+    /// Creates a new run of the given difficulty, with the given rules
+    pub fn with_preset(
+        difficulty: Difficulty,
+        preset: DifficultyPreset,
+        slot_count: u8,
+        seed: u64,
+    ) -> Self {
+        Self {
+            difficulty,
+            slot_count,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            tiles: vec![],
+            slot_press_tick: vec![None; slot_count as usize],
+            slot_held: vec![false; slot_count as usize],
+            tick: 0,
+            last_tile_tick: 0,
+            tile_spawn_time: preset.spawn_interval(preset.start_hits),
+            tile_hit_count: preset.start_hits,
+            windows: preset.windows,
+            scores: preset.scores,
+            judgements: JudgementCounts::default(),
+            points: 0,
+            combo: 0,
+            max_combo: 0,
+            lives: preset.lives,
+            max_lives: preset.lives,
+            slot_clicks: 0,
+            next_tile_id: 0,
+            inputs: vec![],
+            chart: None,
+            schedule: None,
+            events: vec![],
+            ended: false,
+            preset,
+        }
+    }
+
+    /// Creates a new run that spawns the tiles of the chart instead of random tiles
+    pub fn from_chart(chart: Chart) -> Self {
+        let mut sim = Self::new(Difficulty::Normal, chart.slot_count, 0);
+        sim.schedule = Some(ChartSchedule::new(&chart));
+        sim.chart = Some(chart);
+        sim
+    }
+
+    /// Creates the run again from its first tick, with the same rules, slot count and seed
+    pub fn restart(&self) -> Self {
+        let mut sim = match &self.chart {
+            Some(chart) => Self::from_chart(chart.clone()),
+            None => Self::with_preset(
+                self.difficulty,
+                self.preset.clone(),
+                self.slot_count,
+                self.seed,
+            ),
+        };
+        sim.windows = self.windows;
+        sim.scores = self.scores;
+        sim
+    }
+
+    /// Returns the tick the chart being played starts on, which is when its song should start
+    pub fn chart_start_tick(&self) -> Option<u64> {
+        self.schedule.as_ref().map(|schedule| schedule.start_tick)
+    }
+
+    /// Returns the score of the player
+    pub fn get_score(&self) -> i32 {
+        match self.difficulty {
+            // time attack is scored by the tiles hit alone
+            Difficulty::TimeAttack => self.judgements.hits() as i32,
+            _ => self.points - self.slot_clicks,
+        }
+    }
+
+    /// Returns true once the player has run out of lives, every tile of the chart being played is gone, the time of a time attack run is up,
+    /// or the run was ended
+    pub fn is_over(&self) -> bool {
+        let chart_finished = self
+            .schedule
+            .as_ref()
+            .is_some_and(|schedule| schedule.is_finished() && self.tiles.is_empty());
+        let out_of_time = self.time_left() == Some(0.0);
+        self.ended || self.lives < 0 || chart_finished || out_of_time
+    }
+
+    /// Returns the seconds left in a time attack run, or none for every other mode
+    pub fn time_left(&self) -> Option<f32> {
+        match self.difficulty {
+            Difficulty::TimeAttack => {
+                let length = TIME_ATTACK_SECONDS * TICKS_PER_SECOND as u64;
+                Some(ticks_to_secs(length.saturating_sub(self.tick)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Ends the run, which is the only way a zen run ends
+    pub fn end(&mut self) {
+        self.ended = true;
+    }
+
+    /// Returns the seconds the run has lasted in simulation time
+    pub fn time_survived(&self) -> f32 {
+        ticks_to_secs(self.tick)
+    }
+
+    /// Returns the events that have happened since the last call
+    pub fn drain_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Returns the number of ticks a slot counts as pressed for after its key is hit, shorter on hard mode
+    pub fn hit_ticks(&self) -> u64 {
+        secs_to_ticks(self.preset.hit_length)
+    }
+
+    /// Returns the leaderboard category the run is ranked in, or none if it is ranked on the main leaderboard
+    pub fn leader_board_category(&self) -> Option<String> {
+        match (&self.chart, self.difficulty) {
+            (Some(chart), _) => Some(chart.leader_board_category()),
+            (None, Difficulty::Custom) => Some(self.preset.leader_board_category()),
+            (None, Difficulty::Practice) => None,
+            // a mode played with a tuned preset is ranked apart from the runs played with the one it ships with
+            (None, difficulty) if self.preset != difficulty.preset() => {
+                let preset_category = self.preset.leader_board_category();
+                Some(match difficulty.leader_board_category() {
+                    Some(mode_category) => format!("{}_{}", mode_category, preset_category),
+                    None => preset_category,
+                })
+            }
+            (None, difficulty) => difficulty.leader_board_category(),
+        }
+    }
+
+    /// Returns true if the slot was pressed recently enough to count towards hitting a chord
+    pub fn is_slot_active(&self, slot: usize) -> bool {
+        match self.slot_press_tick.get(slot) {
+            Some(Some(press_tick)) => self.tick - press_tick < self.hit_ticks(),
+            _ => false,
+        }
+    }
+
+    /// Returns the score multiplier the current combo gives
+    pub fn multiplier(&self) -> i32 {
+        COMBO_MULTIPLIERS
+            .iter()
+            .find(|(combo, _)| self.combo >= *combo)
+            .map_or(1, |(_, multiplier)| *multiplier)
+    }
+
+    /// Presses the given slot on the current tick, which hits the nearest tile in the slot
+    pub fn press_slot(&mut self, slot: u8) {
+        self.press_slot_at(slot, 0.0);
+    }
+
+    /// Presses the given slot the given fraction of a tick after the current tick, and judges the press at that moment
+    pub fn press_slot_at(&mut self, slot: u8, offset: f32) {
+        if slot >= self.slot_count {
+            return;
+        }
+        let offset = offset.clamp(0.0, 1.0);
+        self.slot_press_tick[slot as usize] = Some(self.tick);
+        self.slot_held[slot as usize] = true;
+        self.slot_clicks += 1; // increment the slot click count when a slot is clicked
+        self.inputs.push(ReplayInput {
+            tick: self.tick,
+            slot,
+            kind: InputKind::Press,
+            offset,
+        });
+        self.events.push(SimEvent::SlotPressed { slot });
+        self.hit_nearest_tile(slot, offset);
+    }
+
+    /// Releases the given slot, which lets go of any hold tile in it
+    pub fn release_slot(&mut self, slot: u8) {
+        match self.slot_held.get_mut(slot as usize) {
+            Some(held) if *held => {
+                *held = false;
+            }
+            _ => return,
+        }
+        self.inputs.push(ReplayInput {
+            tick: self.tick,
+            slot,
+            kind: InputKind::Release,
+            offset: 0.0,
+        });
+        self.events.push(SimEvent::SlotReleased { slot });
+    }
+
+    /// Feeds a recorded input into the simulation
+    pub fn apply_input(&mut self, input: &ReplayInput) {
+        match input.kind {
+            InputKind::Press => self.press_slot_at(input.slot, input.offset),
+            InputKind::Release => self.release_slot(input.slot),
+        }
+    }
+
+    /// Runs a single tick of the simulation
+    pub fn step(&mut self) {
+        self.update_holds();
+        self.update_tile_spawn_time(); // update tile spawning rate
+
+        self.tiles.iter_mut().for_each(|tile| tile.tick()); // tick every tile
+
+        if let Some(schedule) = self.schedule.as_mut() {
+            // charts decide every tile themselves
+            for note in schedule.due(self.tick) {
+                self.push_tile(
+                    Tile::new(self.next_tile_id, note.slot, note.speed).with_kind(note.kind),
+                );
+            }
+        } else if ticks_to_secs(self.tick - self.last_tile_tick) >= self.tile_spawn_time {
+            // decide if we need to spawn a new tile
+            let speed = self.get_tile_speed(); // get a tile speed from the game state
+            self.add_tile(speed);
+            self.last_tile_tick = self.tick; // update the tick the last tile was spawned on
+        }
+
+        self.cleanup_tiles();
+        self.tick += 1;
+    }
+
+    /// Adds a tile in a random slot with the given speed
+    pub fn add_tile(&mut self, speed: f32) {
+        let slot = self.rng.gen_range(0..self.slot_count);
+        self.push_tile(Tile::new(self.next_tile_id, slot, speed));
+    }
+
+    /// Adds the tile to the field, the tile must have been given the next tile id
+    fn push_tile(&mut self, tile: Tile) {
+        self.next_tile_id += 1;
+        self.events.push(SimEvent::TileSpawned {
+            id: tile.id,
+            slot: tile.slot,
+            speed: tile.speed,
+            kind: tile.kind,
+        });
+        self.tiles.push(tile);
+    }
+
+    /// Hits the tile in the slot that is nearest to the middle of the bar at the moment of the press, if one is close enough to be judged.
+    /// A press only ever hits one tile, and a press that hits nothing breaks the combo.
+    /// Chord tiles are only hit once every slot they cover has been pressed, and are judged on the last press
+    fn hit_nearest_tile(&mut self, slot: u8, offset: f32) {
+        let nearest = self
+            .tiles
+            .iter()
+            .enumerate()
+            // hold tiles that are being held have already been hit
+            .filter(|(_, tile)| tile.held.is_none() && tile.slots().contains(&slot))
+            .filter_map(|(index, tile)| {
+                let distance = tile.distance_from_bar_after(offset);
+                self.windows
+                    .judge(distance)
+                    .map(|judgement| (index, distance, judgement))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((index, _, judgement)) = nearest else {
+            self.break_combo();
+            return;
+        };
+
+        let tile = &self.tiles[index];
+        match tile.kind {
+            TileKind::Hold { .. } => {
+                let id = tile.id;
+                self.tiles[index].held = Some(judgement); // hold tiles are kept, and scored once they are finished or let go of
+                self.events.push(SimEvent::HoldStarted { id, judgement });
+            }
+            TileKind::Bomb => {
+                let id = self.tiles.remove(index).id;
+                self.break_combo();
+                self.events.push(SimEvent::BombHit { id });
+                self.lose_life();
+            }
+            TileKind::Chord { .. } => {
+                let all_pressed = tile
+                    .slots()
+                    .iter()
+                    .all(|slot| self.is_slot_active(*slot as usize));
+                if all_pressed {
+                    self.hit_tile(index, judgement);
+                }
+            }
+            TileKind::Normal | TileKind::Bonus => self.hit_tile(index, judgement),
+        }
+    }
+
+    /// Removes the tile at the given index of the tile list as hit with the given judgement, bonus tiles give back a life
+    fn hit_tile(&mut self, index: usize, judgement: Judgement) {
+        let tile = self.tiles.remove(index);
+        self.score_hit(tile.id, judgement);
+        if tile.kind == TileKind::Bonus && self.lives < self.max_lives {
+            self.lives += 1;
+            self.events.push(SimEvent::LifeGained { lives: self.lives });
+        }
+    }
+
+    /// Scores the tile with the given id as hit with the given judgement, which moves the run along the spawn and speed curves
+    fn score_hit(&mut self, id: u32, judgement: Judgement) {
+        self.add_hit(judgement);
+        self.tile_hit_count += 2;
+        self.events.push(SimEvent::TileHit { id, judgement });
+    }
+
+    /// Adds a hit to the combo, then counts the judgement
+    fn add_hit(&mut self, judgement: Judgement) {
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+        self.judge(judgement);
+    }
+
+    /// Finishes the hold tiles whose tail has reached the bar, or that were let go of with their tail close to the bar,
+    /// which scores them as hit with the judgement of their head.
+    /// Letting go before the tail is close to the bar counts as a miss, and only gives the points of the head for the part that was held
+    fn update_holds(&mut self) {
+        let mut finished = vec![];
+        let mut broken = vec![];
+        self.tiles.retain(|tile| {
+            let Some(head) = tile.held else {
+                return true;
+            };
+            let tail_offset = tile.tail_offset();
+            if tail_offset >= 0.0 {
+                // held the whole way
+                finished.push((tile.id, head));
+                return false;
+            }
+            if self.slot_held[tile.slot as usize] {
+                return true;
+            }
+            match self.windows.judge(-tail_offset) {
+                Some(_) => finished.push((tile.id, head)),
+                None => {
+                    let held = (1.0 + tail_offset / tile.length.max(1.0)).clamp(0.0, 1.0);
+                    broken.push((tile.id, head, held));
+                }
+            }
+            false
+        });
+        for (id, judgement) in finished {
+            self.score_hit(id, judgement);
+        }
+        for (id, head, held) in broken {
+            self.break_combo();
+            self.judge(Judgement::Miss);
+            self.points += (self.scores.points(head) as f32 * held).round() as i32;
+            self.events.push(SimEvent::HoldBroken { id });
+        }
+    }
+
+    /// Resets the combo, which ends a sudden death run
+    fn break_combo(&mut self) {
+        self.combo = 0;
+        if self.difficulty == Difficulty::SuddenDeath {
+            self.ended = true;
+        }
+    }
+
+    /// Takes a life from the player, unless the mode has no lives
+    fn lose_life(&mut self) {
+        if self.difficulty.has_lives() {
+            self.lives -= 1;
+            self.events.push(SimEvent::LifeLost { lives: self.lives });
+        }
+    }
+
+    /// Counts the judgement and adds the points it is worth, hits are multiplied by the combo multiplier
+    fn judge(&mut self, judgement: Judgement) {
+        self.judgements.add(judgement);
+        let multiplier = match judgement {
+            Judgement::Miss => 1,
+            _ => self.multiplier(),
+        };
+        self.points += self.scores.points(judgement) * multiplier;
+    }
+
+    /// Removes all the tiles that are off the field, costing a life for each, except for bomb and bonus tiles, or in modes with no lives
+    fn cleanup_tiles(&mut self) {
+        let mut missed = vec![];
+        let mut passed = vec![];
+        self.tiles.retain(|tile| {
+            if tile.is_off_field() {
+                match tile.kind {
+                    TileKind::Bomb | TileKind::Bonus => passed.push(tile.id),
+                    _ => missed.push(tile.id),
+                }
+                return false;
+            }
+            true
+        });
+        for id in passed {
+            self.events.push(SimEvent::TilePassed { id });
+        }
+        for id in missed {
+            self.break_combo();
+            self.judge(Judgement::Miss);
+            self.events.push(SimEvent::TileMissed { id });
+            self.lose_life();
+        }
+    }
+
+    /// Returns the tile speed that new tiles should, picked from the speeds of the preset at the current tile hit count
+    fn get_tile_speed(&mut self) -> f32 {
+        match self.preset.speeds_at(self.tile_hit_count) {
+            // a single speed does not use the rng, so runs of the built in presets stay the same
+            [speed] => *speed,
+            speeds => *speeds.choose(&mut self.rng).unwrap_or(&2.0),
+        }
+    }
+
+    /// Updates tile spawn time based on players tile hit count.
+    fn update_tile_spawn_time(&mut self) {
+        self.tile_spawn_time = self.preset.spawn_interval(self.tile_hit_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The seed every test run is played with, so the slots tiles spawn in are the same every time
+    const SEED: u64 = 42;
+
+    /// Steps the simulation until the first tile on the field is within a perfect judgement of the bar
+    fn step_to_bar(sim: &mut Simulation) {
+        loop {
+            match sim.tiles.first() {
+                Some(tile) if tile.distance_from_bar() <= sim.windows.perfect => return,
+                _ => sim.step(),
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_spawn_at_the_preset_interval() {
+        let mut sim = Simulation::new(Difficulty::Normal, 3, SEED);
+        let interval = secs_to_ticks(sim.preset.spawn_interval(0));
+        for _ in 0..=interval {
+            assert!(sim.tiles.is_empty());
+            sim.step();
+        }
+        assert_eq!(sim.tiles.len(), 1);
+        for _ in 0..interval {
+            sim.step();
+        }
+        assert_eq!(sim.tiles.len(), 2);
+    }
+
+    #[test]
+    fn pressing_a_tile_on_the_bar_hits_it() {
+        let mut sim = Simulation::new(Difficulty::Normal, 3, SEED);
+        step_to_bar(&mut sim);
+        let tile = sim.tiles[0].clone();
+        sim.press_slot(tile.slot);
+        assert!(sim.tiles.iter().all(|other| other.id != tile.id));
+        assert_eq!(sim.judgements.perfect, 1);
+        assert_eq!(sim.combo, 1);
+        assert_eq!(sim.get_score(), sim.scores.perfect - 1);
+        assert!(sim.drain_events().contains(&SimEvent::TileHit {
+            id: tile.id,
+            judgement: Judgement::Perfect,
+        }));
+    }
+
+    #[test]
+    fn pressing_a_slot_with_no_tile_near_the_bar_misses() {
+        let mut sim = Simulation::new(Difficulty::Normal, 3, SEED);
+        step_to_bar(&mut sim);
+        let slot = sim.tiles[0].slot;
+        let tile_count = sim.tiles.len();
+        sim.press_slot((slot + 1) % sim.slot_count);
+        assert_eq!(sim.tiles.len(), tile_count);
+        assert_eq!(sim.judgements.hits(), 0);
+        assert_eq!(sim.get_score(), -1);
+    }
+
+    #[test]
+    fn a_tile_that_falls_off_the_field_costs_a_life() {
+        let mut sim = Simulation::new(Difficulty::Normal, 3, SEED);
+        step_to_bar(&mut sim);
+        let id = sim.tiles[0].id;
+        while sim.tiles.iter().any(|tile| tile.id == id) {
+            sim.step();
+        }
+        assert_eq!(sim.lives, sim.max_lives - 1);
+        assert_eq!(sim.judgements.miss, 1);
+        let events = sim.drain_events();
+        assert!(events.contains(&SimEvent::TileMissed { id }));
+        assert!(events.contains(&SimEvent::LifeLost {
+            lives: sim.max_lives - 1
+        }));
+    }
+
+    #[test]
+    fn the_run_is_over_once_every_life_is_lost() {
+        let mut sim = Simulation::new(Difficulty::Normal, 3, SEED);
+        while !sim.is_over() {
+            assert!(sim.lives >= 0);
+            sim.step();
+        }
+        assert_eq!(sim.lives, -1);
+        assert_eq!(sim.judgements.miss, sim.max_lives as u32 + 1);
+    }
+
+    #[test]
+    fn modes_without_lives_only_end_when_ended() {
+        let mut sim = Simulation::new(Difficulty::Zen, 3, SEED);
+        for _ in 0..secs_to_ticks(60.0) {
+            sim.step();
+        }
+        assert!(sim.judgements.miss > 0);
+        assert!(!sim.is_over());
+        sim.end();
+        assert!(sim.is_over());
+    }
+
+    #[test]
+    fn time_attack_is_over_once_the_time_is_up() {
+        let mut sim = Simulation::new(Difficulty::TimeAttack, 3, SEED);
+        let length = TIME_ATTACK_SECONDS * TICKS_PER_SECOND as u64;
+        for _ in 0..length - 1 {
+            sim.step();
+        }
+        assert!(!sim.is_over());
+        sim.step();
+        assert!(sim.is_over());
+    }
+
+    /// Creates a run of a chart with the given notes, in 3 slots
+    fn chart_sim(notes: &str) -> Simulation {
+        let json = format!(
+            r#"{{ "title": "Test", "slot_count": 3, "notes": {} }}"#,
+            notes
+        );
+        Simulation::from_chart(Chart::from_json(&json).unwrap())
+    }
+
+    #[test]
+    fn a_press_only_hits_the_nearest_tile() {
+        // the two tiles are 30 pixels apart, so both are in the good window together
+        let mut sim = chart_sim(
+            r#"[
+                { "time": 2.0, "slot": 0, "speed": 2.0 },
+                { "time": 2.25, "slot": 0, "speed": 2.0 }
+            ]"#,
+        );
+        step_to_bar(&mut sim);
+        let nearest = sim.tiles[0].id;
+        assert!(sim
+            .windows
+            .judge(sim.tiles[1].distance_from_bar())
+            .is_some());
+        sim.press_slot(0);
+        assert_eq!(sim.judgements.hits(), 1);
+        assert_eq!(sim.tiles.len(), 1);
+        assert_ne!(sim.tiles[0].id, nearest);
+    }
+
+    #[test]
+    fn a_press_that_hits_nothing_breaks_the_combo() {
+        let mut sim = Simulation::new(Difficulty::Normal, 3, SEED);
+        step_to_bar(&mut sim);
+        let slot = sim.tiles[0].slot;
+        sim.press_slot(slot);
+        assert_eq!(sim.combo, 1);
+        sim.press_slot(slot);
+        assert_eq!(sim.combo, 0);
+        assert!(!sim.is_over());
+    }
+
+    #[test]
+    fn a_press_that_hits_nothing_ends_sudden_death() {
+        let mut sim = Simulation::new(Difficulty::SuddenDeath, 3, SEED);
+        step_to_bar(&mut sim);
+        let slot = sim.tiles[0].slot;
+        sim.press_slot(slot);
+        assert!(!sim.is_over());
+        sim.press_slot((slot + 1) % sim.slot_count);
+        assert!(sim.is_over());
+    }
+
+    #[test]
+    fn a_chord_is_hit_once_every_slot_is_pressed() {
+        let mut sim = chart_sim(
+            r#"[{ "time": 2.0, "slot": 0, "kind": { "Chord": { "slots": 5 } }, "speed": 2.0 }]"#,
+        );
+        step_to_bar(&mut sim);
+        sim.press_slot(0);
+        assert_eq!(sim.tiles.len(), 1);
+        assert_eq!(sim.judgements.hits(), 0);
+        sim.press_slot(2);
+        assert!(sim.tiles.is_empty());
+        assert_eq!(sim.judgements.hits(), 1);
+    }
+
+    #[test]
+    fn a_chord_is_not_hit_by_presses_too_far_apart() {
+        let mut sim = chart_sim(
+            r#"[{ "time": 2.0, "slot": 0, "kind": { "Chord": { "slots": 3 } }, "speed": 2.0 }]"#,
+        );
+        step_to_bar(&mut sim);
+        sim.press_slot(0);
+        for _ in 0..sim.hit_ticks() {
+            sim.step();
+        }
+        sim.press_slot(1);
+        assert_eq!(sim.tiles.len(), 1);
+        assert_eq!(sim.judgements.hits(), 0);
+    }
+
+    #[test]
+    fn hitting_a_bomb_costs_a_life() {
+        let mut sim = chart_sim(r#"[{ "time": 2.0, "slot": 1, "kind": "Bomb", "speed": 2.0 }]"#);
+        step_to_bar(&mut sim);
+        let id = sim.tiles[0].id;
+        sim.press_slot(1);
+        assert!(sim.tiles.is_empty());
+        assert_eq!(sim.lives, sim.max_lives - 1);
+        assert_eq!(sim.judgements.hits(), 0);
+        assert!(sim.drain_events().contains(&SimEvent::BombHit { id }));
+    }
+
+    /// A single hold in slot 0 that lasts a second, which gives it a tail 120 pixels long
+    const HOLD: &str =
+        r#"[{ "time": 2.0, "slot": 0, "kind": { "Hold": { "duration": 1.0 } }, "speed": 2.0 }]"#;
+
+    #[test]
+    fn a_hold_held_to_the_end_is_scored_once_by_its_head() {
+        let mut sim = chart_sim(HOLD);
+        step_to_bar(&mut sim);
+        let id = sim.tiles[0].id;
+        sim.press_slot(0);
+        assert_eq!(sim.combo, 0);
+        assert_eq!(sim.points, 0);
+        while !sim.tiles.is_empty() {
+            sim.step();
+        }
+        assert_eq!(sim.judgements.perfect, 1);
+        assert_eq!(sim.judgements.hits(), 1);
+        assert_eq!(sim.combo, 1);
+        assert_eq!(sim.points, sim.scores.perfect);
+        assert_eq!(sim.tile_hit_count, 2);
+        assert!(sim.drain_events().contains(&SimEvent::TileHit {
+            id,
+            judgement: Judgement::Perfect,
+        }));
+    }
+
+    #[test]
+    fn letting_go_of_a_hold_early_only_scores_the_part_held() {
+        let mut sim = chart_sim(HOLD);
+        step_to_bar(&mut sim);
+        let id = sim.tiles[0].id;
+        sim.press_slot(0);
+        for _ in 0..25 {
+            sim.step();
+        }
+        sim.release_slot(0);
+        sim.step();
+        assert!(sim.tiles.is_empty());
+        assert_eq!(sim.judgements.hits(), 0);
+        assert_eq!(sim.judgements.miss, 1);
+        assert_eq!(sim.combo, 0);
+        assert!(sim.points > 0 && sim.points < sim.scores.perfect);
+        assert_eq!(sim.tile_hit_count, 0);
+        assert_eq!(sim.lives, sim.max_lives);
+        assert!(sim.drain_events().contains(&SimEvent::HoldBroken { id }));
+    }
+
+    #[test]
+    fn letting_go_of_a_hold_with_its_tail_on_the_bar_finishes_it() {
+        let mut sim = chart_sim(HOLD);
+        step_to_bar(&mut sim);
+        sim.press_slot(0);
+        while sim.windows.judge(-sim.tiles[0].tail_offset()).is_none() {
+            sim.step();
+        }
+        sim.release_slot(0);
+        sim.step();
+        assert!(sim.tiles.is_empty());
+        assert_eq!(sim.judgements.perfect, 1);
+        assert_eq!(sim.judgements.miss, 0);
+        assert_eq!(sim.combo, 1);
+        assert_eq!(sim.points, sim.scores.perfect);
+        assert_eq!(sim.tile_hit_count, 2);
+    }
+
+    #[test]
+    fn hitting_a_bonus_gives_back_a_life() {
+        let mut sim = chart_sim(
+            r#"[
+                { "time": 2.0, "slot": 0, "speed": 2.0 },
+                { "time": 8.0, "slot": 1, "kind": "Bonus", "speed": 2.0 }
+            ]"#,
+        );
+        while sim.lives == sim.max_lives {
+            sim.step();
+        }
+        step_to_bar(&mut sim);
+        sim.press_slot(1);
+        assert!(sim.tiles.is_empty());
+        assert_eq!(sim.lives, sim.max_lives);
+        assert!(sim.drain_events().contains(&SimEvent::LifeGained {
+            lives: sim.max_lives
+        }));
+    }
+
+    #[test]
+    fn a_missed_bonus_costs_nothing() {
+        let mut sim = chart_sim(r#"[{ "time": 2.0, "slot": 1, "kind": "Bonus", "speed": 2.0 }]"#);
+        while !sim.is_over() {
+            sim.step();
+        }
+        assert_eq!(sim.lives, sim.max_lives);
+        assert_eq!(sim.judgements.miss, 0);
+        assert_eq!(sim.combo, 0);
+    }
+
+    #[test]
+    fn a_mode_played_with_a_tuned_preset_is_ranked_apart() {
+        let shipped = Simulation::new(Difficulty::Zen, 4, SEED);
+        assert_eq!(
+            shipped.leader_board_category(),
+            Some("mode_zen".to_string())
+        );
+        let mut preset = DifficultyPreset::normal();
+        preset.lives += 1;
+        let category = preset.leader_board_category();
+        let tuned = Simulation::with_preset(Difficulty::Normal, preset.clone(), 4, SEED);
+        assert_eq!(tuned.leader_board_category(), Some(category.clone()));
+        let tuned = Simulation::with_preset(Difficulty::Zen, preset, 4, SEED);
+        assert_eq!(
+            tuned.leader_board_category(),
+            Some(format!("mode_zen_{}", category))
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use crate::tile::TileKind;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LiveGameInfo {
+    /// The id the server gave the game, used to spectate it
+    pub id: u64,
+    /// The name of the player playing the game
+    pub user_name: String,
+    /// The slot count the game is being played with
+    pub slot_count: u8,
+}
+
+/// Something that happened in a live game, streamed from the player to every spectator
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum LiveEvent {
+    /// A tile was spawned at the top of the screen
+    TileSpawned {
+        id: u32,
+        slot: u8,
+        speed: f32,
+        /// The type of the tile, normal when sent by an older game
+        #[serde(default)]
+        kind: TileKind,
+        /// The number of ticks the tile has already fallen for, only more than 0 when a spectator who joined late is sent the tiles already on the field
+        #[serde(default)]
+        ticks: u64,
+    },
+    /// The player hit the tile with the given id
+    TileHit { id: u32 },
+    /// The tile with the given id went off the bottom of the screen
+    TileMissed { id: u32 },
+    /// The player pressed the key for the given slot
+    SlotPressed { slot: u8 },
+    /// The current score and lives of the player, and the tick their game is on
+    Status {
+        score: i32,
+        lives: i32,
+        #[serde(default)]
+        tick: u64,
+    },
+}
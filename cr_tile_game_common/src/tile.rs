@@ -0,0 +1,120 @@
+use crate::judgement::Judgement;
+use crate::timestep::TICKS_PER_SECOND;
+use serde::{Deserialize, Serialize};
+
+/// The height of the tile, for rendering and spacing, as well as duration that the tile can be hit
+pub const TILE_HEIGHT: f32 = 100.0;
+/// The location on the y axis of the top of the hit bar
+pub const HIT_BAR: f32 = 550.0;
+/// The height of the hit bar, larger hit distance would make hitting tiles easier
+pub const HIT_DISTANCE: f32 = 25.0;
+/// The location on the y axis representing the middle of the hit bar, used for distance calculations
+pub const MIDDLE_BAR: f32 = HIT_BAR + (HIT_DISTANCE / 2.0);
+/// The distance at which a tile has gone off the bottom of the playing field
+pub const FIELD_HEIGHT: f32 = 600.0;
+
+/// The type of a tile, which decides how it has to be hit
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum TileKind {
+    /// A tile that is hit by pressing its slot while it is on the bar
+    #[default]
+    Normal,
+    /// A long tile that is pressed when its head is on the bar, and held until its tail reaches the bar the given number of seconds later
+    Hold { duration: f32 },
+    /// A wide tile that covers every slot whose bit is set in `slots`, and is hit by pressing all of them together
+    Chord { slots: u8 },
+    /// A tile that costs a life if it is hit, and should be left to fall off the field
+    Bomb,
+    /// A tile that gives back a lost life when it is hit, and costs nothing if it is missed
+    Bonus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A struct representing the tiles within the game.
+pub struct Tile {
+    /// The id of the tile, unique within a single game
+    pub id: u32,
+    /// The distance of the tile to the bottom of the screen (the y coordinate)
+    pub distance: f32,
+    /// The slot that the tile is in
+    pub slot: u8,
+    /// The speed of the tile in units per simulation tick.
+    pub speed: f32,
+    /// The type of the tile
+    pub kind: TileKind,
+    /// The length of the tail of a hold tile, which reaches up from the head, 0 for every other tile
+    pub length: f32,
+    /// The judgement the head of a hold tile was hit with, while the hold is being held
+    pub held: Option<Judgement>,
+}
+
+impl Tile {
+    /// Generates a new tile at the top of the screen in the given slot.
+    pub fn new(id: u32, slot: u8, speed: f32) -> Self {
+        Self {
+            id,
+            distance: -TILE_HEIGHT,
+            slot,
+            speed,
+            kind: TileKind::Normal,
+            length: 0.0,
+            held: None,
+        }
+    }
+
+    /// Changes the type of the tile, hold tiles get a tail long enough to reach the bar once their duration is over
+    pub fn with_kind(mut self, kind: TileKind) -> Self {
+        self.kind = kind;
+        self.length = match kind {
+            TileKind::Hold { duration } => duration * TICKS_PER_SECOND as f32 * self.speed,
+            _ => 0.0,
+        };
+        self
+    }
+
+    /// Ticks the movement of the tile by a single simulation tick
+    pub fn tick(&mut self) {
+        self.distance += self.speed;
+    }
+
+    /// Returns the distance between the middle of the tile and the middle of the bar
+    pub fn distance_from_bar(&self) -> f32 {
+        let middle_y = self.distance + (TILE_HEIGHT / 2.0); // middle of the tile in terms of its y coordinate
+        (middle_y - MIDDLE_BAR).abs()
+    }
+
+    /// Returns the distance between the middle of the tile and the middle of the bar, the given fraction of a tick from now
+    pub fn distance_from_bar_after(&self, ticks: f32) -> f32 {
+        let middle_y = self.distance + (self.speed * ticks) + (TILE_HEIGHT / 2.0);
+        (middle_y - MIDDLE_BAR).abs()
+    }
+
+    /// Returns every slot the tile covers, which is more than one only for chord tiles
+    pub fn slots(&self) -> Vec<u8> {
+        match self.kind {
+            TileKind::Chord { slots } => (0..u8::BITS as u8)
+                .filter(|slot| slots & (1 << slot) != 0)
+                .collect(),
+            _ => vec![self.slot],
+        }
+    }
+
+    /// Returns true if the tile is overlapping the bar in the given slot
+    pub fn is_hit(&self, slot: u8) -> bool {
+        if !self.slots().contains(&slot) {
+            return false;
+        }
+
+        self.distance_from_bar() <= ((TILE_HEIGHT + HIT_DISTANCE) / 2.0) // if the distance to the bar is <= the size of the bar plus the size of the tile
+    }
+
+    /// Returns how far the end of the tail of the tile is below the middle of the bar, negative while it is still above the bar
+    pub fn tail_offset(&self) -> f32 {
+        self.distance + (TILE_HEIGHT / 2.0) - self.length - MIDDLE_BAR
+    }
+
+    /// Returns true if the tile has gone off the bottom of the playing field, including its tail
+    pub fn is_off_field(&self) -> bool {
+        self.distance - self.length >= FIELD_HEIGHT
+    }
+}
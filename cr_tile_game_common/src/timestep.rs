@@ -0,0 +1,66 @@
+use std::time::Instant;
+
+/// The number of times per second the simulation is stepped, regardless of the frame rate
+pub const TICKS_PER_SECOND: u32 = 60;
+/// The length of a single simulation step in seconds
+pub const TICK_LENGTH: f64 = 1.0 / TICKS_PER_SECOND as f64;
+/// The most time a single frame can add to the clock, so a long stall does not make the simulation try to catch up all at once
+pub const MAX_FRAME_TIME: f64 = 0.25;
+
+/// Converts a number of ticks to seconds
+pub fn ticks_to_secs(ticks: u64) -> f32 {
+    (ticks as f64 * TICK_LENGTH) as f32
+}
+
+/// Converts a duration in seconds to the number of ticks it lasts, rounding up so it is at least one tick long
+pub fn secs_to_ticks(secs: f32) -> u64 {
+    ((secs as f64 / TICK_LENGTH).ceil() as u64).max(1)
+}
+
+#[derive(Debug, Clone)]
+/// Accumulates real time from a monotonic clock, and hands it out in fixed size ticks
+pub struct FixedTimestep {
+    /// The last time the clock was advanced
+    last_update: Instant,
+    /// Time that has passed but has not been handed out as a tick yet
+    accumulator: f64,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FixedTimestep {
+    /// Creates a clock starting now
+    pub fn new() -> Self {
+        Self {
+            last_update: Instant::now(),
+            accumulator: 0.0,
+        }
+    }
+
+    /// Advances the clock to now, and returns how many ticks should be simulated to catch up
+    pub fn advance(&mut self) -> u32 {
+        let now = Instant::now();
+        let frame_time = now
+            .duration_since(self.last_update)
+            .as_secs_f64()
+            .min(MAX_FRAME_TIME);
+        self.last_update = now;
+        self.accumulator += frame_time;
+
+        let mut ticks = 0;
+        while self.accumulator >= TICK_LENGTH {
+            self.accumulator -= TICK_LENGTH;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Returns how far the clock is into the next tick, as a fraction of a tick
+    pub fn fraction(&self) -> f32 {
+        (self.accumulator / TICK_LENGTH) as f32
+    }
+}
@@ -0,0 +1,86 @@
+use cr_tile_game_common::leader_board_stat::{LeaderBoardEntry, LeaderBoardList};
+use cr_tile_game_common::packet::{CategoryScorePacket, ServerPacket};
+use smol_db_client::SmolDbClient;
+
+/// The db that stores every score that is not on the main leaderboard, keyed by the category and the login hash of the player
+pub const CATEGORY_DB_NAME: &str = "cr_tile_game_category_db";
+
+/// The longest category name that is accepted
+const MAX_CATEGORY_LENGTH: usize = 64;
+
+/// Returns true if the category can be used as part of a db key
+fn is_valid_category(category: &str) -> bool {
+    !category.is_empty()
+        && category.len() <= MAX_CATEGORY_LENGTH
+        && category
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The key a score of the player with the given login hash is stored under in the category db
+fn category_key(category: &str, login_hash: &str) -> String {
+    format!("{}#{}", category, login_hash)
+}
+
+/// Returns every score submitted to the given category
+pub fn category_leader_board(client: &mut SmolDbClient, category: &str) -> Option<LeaderBoardList> {
+    if !is_valid_category(category) {
+        return None;
+    }
+    let prefix = category_key(category, "");
+    match client.list_db_contents_generic::<LeaderBoardEntry>(CATEGORY_DB_NAME) {
+        Ok(resp) => Some(LeaderBoardList::new(
+            resp.into_iter()
+                .filter(|(key, _)| key.starts_with(&prefix))
+                .map(|(_, entry)| entry)
+                .collect(),
+        )),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            None
+        }
+    }
+}
+
+/// Stores the score if it is the best score of the player in the category, and replies with the leaderboard of the category
+pub fn submit_category_score(
+    client: &mut SmolDbClient,
+    packet: CategoryScorePacket,
+) -> ServerPacket {
+    if !is_valid_category(&packet.category) {
+        return ServerPacket::ErrorState;
+    }
+
+    let login_info = &packet.data.login_info;
+    let login_hash = login_info.hash().to_string();
+    let key = category_key(&packet.category, &login_hash);
+    let entry = LeaderBoardEntry::new(
+        login_info.user_name.clone(),
+        packet.data.score,
+        login_hash[0..6].to_string(),
+    );
+
+    let best = match client.list_db_contents_generic::<LeaderBoardEntry>(CATEGORY_DB_NAME) {
+        Ok(mut resp) => resp.remove(&key),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            return ServerPacket::ErrorState;
+        }
+    };
+
+    let is_best = match best {
+        None => true,
+        Some(best) => entry.beats(&best),
+    };
+    if is_best {
+        if let Err(err) = client.write_db_generic(CATEGORY_DB_NAME, &key, entry) {
+            eprintln!("{:?}", err);
+            return ServerPacket::ErrorState;
+        }
+    }
+
+    match category_leader_board(client, &packet.category) {
+        None => ServerPacket::ErrorState,
+        Some(list) => ServerPacket::LeaderBoard(list),
+    }
+}
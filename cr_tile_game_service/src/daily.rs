@@ -0,0 +1,136 @@
+use cr_tile_game_common::daily::DailyChallenge;
+use cr_tile_game_common::leader_board_stat::{LeaderBoardEntry, LeaderBoardList};
+use cr_tile_game_common::packet::{DailyAttemptPacket, DailyScorePacket, LoginInfo, ServerPacket};
+use smol_db_client::SmolDbClient;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The db that stores every daily challenge score, keyed by the day and the login hash of the player
+pub const DAILY_DB_NAME: &str = "cr_tile_game_daily_db";
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+/// Returns the challenge for the current day
+pub fn daily_challenge() -> DailyChallenge {
+    let day = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / SECONDS_PER_DAY;
+    DailyChallenge::for_day(day)
+}
+
+/// The prefix of every key in the daily db that belongs to the given day
+fn day_prefix(day: u64) -> String {
+    format!("{}_", day)
+}
+
+/// Returns every score submitted for the given day
+pub fn daily_leader_board(client: &mut SmolDbClient, day: u64) -> Option<LeaderBoardList> {
+    let prefix = day_prefix(day);
+    match client.list_db_contents_generic::<LeaderBoardEntry>(DAILY_DB_NAME) {
+        Ok(resp) => Some(LeaderBoardList::new(
+            resp.into_iter()
+                .filter(|(key, _)| key.starts_with(&prefix))
+                .map(|(_, entry)| entry)
+                .collect(),
+        )),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            None
+        }
+    }
+}
+
+/// Returns true if scores for the challenge of the given day are still accepted, yesterdays is allowed for runs that cross midnight
+fn is_day_open(day: u64) -> bool {
+    let today = daily_challenge().day;
+    day <= today && day + 1 >= today
+}
+
+/// Returns the key of the score of the player for the given day in the daily db
+pub fn daily_key(day: u64, login_info: &LoginInfo) -> String {
+    format!("{}{}", day_prefix(day), login_info.hash())
+}
+
+/// Returns true if the player already has a score stored for the given day, or none if the db could not be read
+fn has_played(client: &mut SmolDbClient, key: &str) -> Option<bool> {
+    match client.list_db_contents_generic::<LeaderBoardEntry>(DAILY_DB_NAME) {
+        Ok(resp) => Some(resp.contains_key(key)),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            None
+        }
+    }
+}
+
+/// Stores the given score for the player on the given day
+fn write_daily_score(
+    client: &mut SmolDbClient,
+    key: &str,
+    login_info: &LoginInfo,
+    score: i32,
+) -> bool {
+    let login_hash = login_info.hash().to_string();
+    let entry = LeaderBoardEntry::new(
+        login_info.user_name.clone(),
+        score,
+        login_hash[0..6].to_string(),
+    );
+    match client.write_db_generic(DAILY_DB_NAME, key, entry) {
+        Ok(_) => true,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            false
+        }
+    }
+}
+
+/// Stores a score of 0 if this is the first attempt of the player for that day, so leaving the run early still uses up the ranked attempt.
+/// Replies with true if this is the ranked attempt
+pub fn start_daily_attempt(client: &mut SmolDbClient, packet: &DailyAttemptPacket) -> ServerPacket {
+    if !is_day_open(packet.day) {
+        return ServerPacket::ErrorState;
+    }
+    let key = daily_key(packet.day, &packet.login_info);
+    match has_played(client, &key) {
+        None => ServerPacket::ErrorState,
+        Some(true) => ServerPacket::DailyAttemptStarted(false),
+        Some(false) => match write_daily_score(client, &key, &packet.login_info, 0) {
+            true => ServerPacket::DailyAttemptStarted(true),
+            false => ServerPacket::ErrorState,
+        },
+    }
+}
+
+/// Stores the score if it is the first attempt of the player for that day, and replies with the leaderboard for that day.
+/// `owns_attempt` is true if the ranked attempt was started on the same connection, in which case the score replaces the 0 stored when it started.
+/// Scores for a challenge that is no longer current are rejected, other than yesterdays to allow for runs that cross midnight.
+pub fn submit_daily_score(
+    client: &mut SmolDbClient,
+    packet: DailyScorePacket,
+    owns_attempt: bool,
+) -> ServerPacket {
+    if !is_day_open(packet.day) {
+        return ServerPacket::ErrorState;
+    }
+
+    let login_info = &packet.data.login_info;
+    let key = daily_key(packet.day, login_info);
+
+    let already_played = match has_played(client, &key) {
+        None => return ServerPacket::ErrorState,
+        Some(played) => played && !owns_attempt,
+    };
+
+    if !already_played && !write_daily_score(client, &key, login_info, packet.data.score) {
+        return ServerPacket::ErrorState;
+    }
+
+    match daily_leader_board(client, packet.day) {
+        None => ServerPacket::ErrorState,
+        Some(list) => match already_played {
+            true => ServerPacket::DailyAttemptUsed(list),
+            false => ServerPacket::LeaderBoard(list),
+        },
+    }
+}
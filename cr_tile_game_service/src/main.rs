@@ -1,18 +1,41 @@
+use category::{category_leader_board, submit_category_score, CATEGORY_DB_NAME};
+use cr_tile_game_common::discovery::{DiscoveryPacket, DiscoveryResponse, DISCOVERY_PORT};
 use cr_tile_game_common::leader_board_stat::{LeaderBoardEntry, LeaderBoardList};
-use cr_tile_game_common::packet::{ClientPacket, LoginInfo, ServerPacket};
+use cr_tile_game_common::packet::{drain_packets, ClientPacket, LoginInfo, ServerPacket};
+use daily::{
+    daily_challenge, daily_key, daily_leader_board, start_daily_attempt, submit_daily_score,
+    DAILY_DB_NAME,
+};
 use smol_db_client::client_error::ClientError;
 use smol_db_client::db_settings::DBSettings;
 use smol_db_client::DBPacketResponseError::DBAlreadyExists;
 use smol_db_client::{DBSuccessResponse, SmolDbClient};
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use spectate::{
+    end_live_game, list_live_games, relay_live_events, spectate, start_live_game, stop_spectating,
+    LiveGames,
+};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{TcpListener, TcpStream, UdpSocket};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::{sleep, JoinHandle};
 use std::time::Duration;
+use versus::{join_versus, leave_versus, relay_versus_update, VersusQueue, VersusSlot};
+use writer::PacketWriter;
+
+mod category;
+mod daily;
+mod spectate;
+mod versus;
+mod writer;
 
 const DB_NAME: &str = "cr_tile_game_db";
 const DB_KEY: &str = "cr_tile_game_service";
+/// The tcp port that players connect to
+const SERVICE_PORT: u16 = 8114;
+/// The environment variable that can be used to change the name the service advertises on the local network
+const SERVICE_NAME_VAR: &str = "CR_TILE_SERVICE_NAME";
 
 fn setup_client(client: &mut SmolDbClient) {
     // set access key to client
@@ -30,12 +53,19 @@ fn setup_client(client: &mut SmolDbClient) {
         }
     }
 
-    // validate that the db is present in the list
+    // validate that every db is present in the list
+    for db_name in [DB_NAME, DAILY_DB_NAME, CATEGORY_DB_NAME] {
+        create_db_if_missing(client, db_name);
+    }
+}
+
+/// Creates the db with the given name, if it does not already exist
+fn create_db_if_missing(client: &mut SmolDbClient, db_name: &str) {
     match client.list_db() {
         Ok(list) => {
-            if !list.iter().any(|db_info| db_info.get_db_name() == DB_NAME) {
+            if !list.iter().any(|db_info| db_info.get_db_name() == db_name) {
                 match client.create_db(
-                    DB_NAME,
+                    db_name,
                     DBSettings::new(
                         Duration::from_secs(30),
                         (false, false, false),
@@ -45,7 +75,7 @@ fn setup_client(client: &mut SmolDbClient) {
                     ),
                 ) {
                     Ok(_) => {
-                        println!("DB Created...");
+                        println!("DB Created: {}", db_name);
                     }
                     Err(err) => {
                         assert_eq!(err, ClientError::DBResponseError(DBAlreadyExists));
@@ -59,8 +89,51 @@ fn setup_client(client: &mut SmolDbClient) {
     }
 }
 
+/// Starts a thread that answers discovery broadcasts from clients on the local network
+fn start_discovery_responder() -> JoinHandle<()> {
+    let name = std::env::var(SERVICE_NAME_VAR).unwrap_or_else(|_| DB_KEY.to_string());
+
+    thread::spawn(move || {
+        let socket = match UdpSocket::bind(("0.0.0.0", DISCOVERY_PORT)) {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("Unable to start discovery responder: {}", err);
+                return;
+            }
+        };
+        println!(
+            "Answering discovery requests on udp port {}",
+            DISCOVERY_PORT
+        );
+
+        let response = serde_json::to_vec(&DiscoveryPacket::Response(DiscoveryResponse {
+            name,
+            port: SERVICE_PORT,
+        }))
+        .unwrap();
+
+        let mut buf: [u8; 1024] = [0; 1024];
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((read_length, src)) => {
+                    if let Ok(DiscoveryPacket::Request) =
+                        serde_json::from_slice::<DiscoveryPacket>(&buf[0..read_length])
+                    {
+                        if let Err(err) = socket.send_to(&response, src) {
+                            println!("{}", err);
+                        }
+                    }
+                }
+                Err(err) => {
+                    println!("Discovery socket read failed: {}", err);
+                }
+            }
+        }
+    })
+}
+
 fn main() {
-    let listener = TcpListener::bind("0.0.0.0:8114").unwrap();
+    let listener = TcpListener::bind(("0.0.0.0", SERVICE_PORT)).unwrap();
 
     let mut client = {
         let mut count = 0;
@@ -89,17 +162,28 @@ fn main() {
     setup_client(&mut client);
 
     let db_client = Arc::new(Mutex::new(client));
+    let versus_queue: VersusQueue = Arc::new(Mutex::new(None));
+    let live_games: LiveGames = Arc::new(Mutex::new(HashMap::new()));
     let mut thread_vec = vec![];
 
-    println!("Listening for players on port 8114");
+    start_discovery_responder();
+
+    println!("Listening for players on port {}", SERVICE_PORT);
 
     for income in listener.incoming() {
         thread_vec.retain(|thread: &JoinHandle<()>| !thread.is_finished());
 
         let db_client_clone = db_client.clone();
+        let versus_queue_clone = versus_queue.clone();
+        let live_games_clone = live_games.clone();
         let handle = thread::spawn(move || {
             let stream = income.expect("Failed to receive tcp stream");
-            handle_client(stream, db_client_clone);
+            handle_client(
+                stream,
+                db_client_clone,
+                versus_queue_clone,
+                live_games_clone,
+            );
         });
 
         thread_vec.push(handle);
@@ -110,168 +194,285 @@ fn main() {
     }
 }
 
-fn handle_client(mut stream: TcpStream, client: Arc<Mutex<SmolDbClient>>) {
+fn handle_client(
+    mut stream: TcpStream,
+    client: Arc<Mutex<SmolDbClient>>,
+    versus_queue: VersusQueue,
+    live_games: LiveGames,
+) {
     let mut buf: [u8; 1024] = [0; 1024];
+    // bytes read from the stream that have not formed a full packet yet
+    let mut pending: Vec<u8> = vec![];
     let ip = stream.peer_addr().expect("Unable to get peer address").ip();
+    // every packet sent to this client goes through the writer, including packets sent from the threads of other clients
+    let writer = match PacketWriter::new(&stream) {
+        Ok(writer) => writer,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let versus_slot: VersusSlot = Arc::new(Mutex::new(None));
+    // the id of the live game this client is playing, and the id of the game they are spectating
+    let mut live_game: Option<u64> = None;
+    let mut spectating: Option<u64> = None;
+    // the key of the ranked daily attempt started on this connection, which its score is stored under once it is submitted
+    let mut daily_attempt: Option<String> = None;
 
     let mut login_info: LoginInfo;
 
     dbg!(ip.to_string());
 
-    loop {
+    'connection: loop {
         match stream.read(&mut buf) {
+            Ok(0) => {
+                // the client closed the connection
+                break;
+            }
             Ok(read_length) => {
-                if read_length > 0 {
-                    match serde_json::from_slice::<ClientPacket>(&buf[0..read_length]) {
-                        Ok(ref packet) => {
-                            match packet {
-                                ClientPacket::SubmitDataPacket(packet) => {
-                                    // db here
-                                    login_info = packet.login_info.clone();
-                                    let db_location = login_info.hash().to_string();
-                                    let mut lock = client.lock().unwrap();
-                                    let discriminator = db_location[0..6].to_string();
-                                    let entry = LeaderBoardEntry::new(
-                                        login_info.user_name.clone(),
-                                        packet.score,
-                                        discriminator,
-                                    );
+                pending.extend_from_slice(&buf[0..read_length]);
+                for packet in drain_packets::<ClientPacket>(&mut pending) {
+                    match packet {
+                        ClientPacket::SubmitDataPacket(packet) => {
+                            // db here
+                            login_info = packet.login_info.clone();
+                            let db_location = login_info.hash().to_string();
+                            let mut lock = client.lock().unwrap();
+                            let discriminator = db_location[0..6].to_string();
+                            let entry = LeaderBoardEntry::new(
+                                login_info.user_name.clone(),
+                                packet.score,
+                                discriminator,
+                            );
 
-                                    let content_opt = {
-                                        match lock
-                                            .list_db_contents_generic::<LeaderBoardEntry>(DB_NAME)
-                                        {
-                                            Ok(mut resp) => {
-                                                match resp.get(db_location.as_str()) {
-                                                    None => {
-                                                        match lock.write_db_generic(
-                                                            DB_NAME,
-                                                            db_location.as_str(),
-                                                            entry.clone(),
-                                                        ) {
-                                                            Ok(resp_write) => match resp_write {
-                                                                _ => {
-                                                                    resp.insert(
-                                                                        db_location.to_string(),
-                                                                        entry,
-                                                                    );
-                                                                }
-                                                            },
-                                                            Err(_) => {
-                                                                break;
-                                                            }
-                                                        }
+                            let content_opt = {
+                                match lock.list_db_contents_generic::<LeaderBoardEntry>(DB_NAME) {
+                                    Ok(mut resp) => {
+                                        match resp.get(db_location.as_str()) {
+                                            None => {
+                                                match lock.write_db_generic(
+                                                    DB_NAME,
+                                                    db_location.as_str(),
+                                                    entry.clone(),
+                                                ) {
+                                                    Ok(_) => {
+                                                        resp.insert(db_location.to_string(), entry);
                                                     }
-                                                    Some(db_entry) => {
-                                                        if entry.get_score() > db_entry.get_score()
-                                                        {
-                                                            match lock.write_db_generic(
-                                                                DB_NAME,
-                                                                db_location.as_str(),
-                                                                entry.clone(),
-                                                            ) {
-                                                                Ok(resp_write) => {
-                                                                    match resp_write {
-                                                                        _ => {
-                                                                            resp.insert(
-                                                                                db_location
-                                                                                    .to_string(),
-                                                                                entry,
-                                                                            );
-                                                                        }
-                                                                    }
-                                                                }
-                                                                Err(_) => {
-                                                                    break;
-                                                                }
-                                                            }
-                                                        }
+                                                    Err(_) => {
+                                                        break 'connection;
                                                     }
                                                 }
-
-                                                let list = resp
-                                                    .into_values()
-                                                    .collect::<Vec<LeaderBoardEntry>>();
-                                                Some(LeaderBoardList::new(list))
-                                            }
-                                            Err(err) => {
-                                                eprintln!("{:?}", err);
-                                                let _ = lock.delete_data(
-                                                    DB_NAME,
-                                                    login_info.hash().to_string().as_str(),
-                                                );
-                                                None
-                                            }
-                                        }
-                                    };
-
-                                    match content_opt {
-                                        None => {
-                                            let ser =
-                                                serde_json::to_string(&ServerPacket::ErrorState)
-                                                    .unwrap();
-                                            match stream.write(ser.as_bytes()) {
-                                                Ok(_) => {}
-                                                Err(err) => {
-                                                    println!("{}", err);
-                                                    break;
-                                                }
                                             }
-                                        }
-                                        Some(content) => {
-                                            let ser = serde_json::to_string(
-                                                &ServerPacket::LeaderBoard(content),
-                                            )
-                                            .unwrap();
-                                            match stream.write(ser.as_bytes()) {
-                                                Ok(_) => {}
-                                                Err(err) => {
-                                                    println!("{}", err);
-                                                    break;
+                                            Some(db_entry) => {
+                                                if entry.beats(db_entry) {
+                                                    match lock.write_db_generic(
+                                                        DB_NAME,
+                                                        db_location.as_str(),
+                                                        entry.clone(),
+                                                    ) {
+                                                        Ok(_) => {
+                                                            resp.insert(
+                                                                db_location.to_string(),
+                                                                entry,
+                                                            );
+                                                        }
+                                                        Err(_) => {
+                                                            break 'connection;
+                                                        }
+                                                    }
                                                 }
                                             }
                                         }
+
+                                        let list =
+                                            resp.into_values().collect::<Vec<LeaderBoardEntry>>();
+                                        Some(LeaderBoardList::new(list))
+                                    }
+                                    Err(err) => {
+                                        eprintln!("{:?}", err);
+                                        let _ = lock.delete_data(
+                                            DB_NAME,
+                                            login_info.hash().to_string().as_str(),
+                                        );
+                                        None
                                     }
                                 }
-                                ClientPacket::GetLeaderBoardsList => {
-                                    let mut lock = client.lock().unwrap();
-                                    match lock.list_db_contents_generic::<LeaderBoardEntry>(DB_NAME)
-                                    {
-                                        Ok(resp) => {
-                                            let list = resp
-                                                .into_values()
-                                                .collect::<Vec<LeaderBoardEntry>>();
-                                            let ser =
-                                                serde_json::to_string(&ServerPacket::LeaderBoard(
-                                                    LeaderBoardList::new(list),
-                                                ))
-                                                .unwrap();
-                                            match stream.write(ser.as_bytes()) {
-                                                Ok(_) => {}
-                                                Err(err) => {
-                                                    println!("{}", err);
-                                                    break;
-                                                }
-                                            }
+                            };
+
+                            match content_opt {
+                                None => match writer.send(&ServerPacket::ErrorState) {
+                                    Ok(_) => {}
+                                    Err(err) => {
+                                        println!("{}", err);
+                                        break 'connection;
+                                    }
+                                },
+                                Some(content) => {
+                                    match writer.send(&ServerPacket::LeaderBoard(content)) {
+                                        Ok(_) => {}
+                                        Err(err) => {
+                                            println!("{}", err);
+                                            break 'connection;
                                         }
-                                        Err(_) => {
-                                            let ser =
-                                                serde_json::to_string(&ServerPacket::ErrorState)
-                                                    .unwrap();
-                                            match stream.write(ser.as_bytes()) {
-                                                Ok(_) => {}
-                                                Err(err) => {
-                                                    println!("{}", err);
-                                                    break;
-                                                }
-                                            }
+                                    }
+                                }
+                            }
+                        }
+                        ClientPacket::GetLeaderBoardsList => {
+                            let mut lock = client.lock().unwrap();
+                            match lock.list_db_contents_generic::<LeaderBoardEntry>(DB_NAME) {
+                                Ok(resp) => {
+                                    let list =
+                                        resp.into_values().collect::<Vec<LeaderBoardEntry>>();
+                                    match writer.send(&ServerPacket::LeaderBoard(
+                                        LeaderBoardList::new(list),
+                                    )) {
+                                        Ok(_) => {}
+                                        Err(err) => {
+                                            println!("{}", err);
+                                            break 'connection;
                                         }
                                     }
                                 }
+                                Err(_) => match writer.send(&ServerPacket::ErrorState) {
+                                    Ok(_) => {}
+                                    Err(err) => {
+                                        println!("{}", err);
+                                        break 'connection;
+                                    }
+                                },
                             }
                         }
-                        Err(err) => {
-                            println!("{}", err);
+                        ClientPacket::JoinVersus(join) => {
+                            join_versus(&versus_queue, &versus_slot, &writer, join);
+                        }
+                        ClientPacket::LeaveVersus => {
+                            leave_versus(&versus_queue, &versus_slot, &writer);
+                        }
+                        ClientPacket::VersusUpdate(status) => {
+                            relay_versus_update(&versus_slot, &writer, status);
+                        }
+                        ClientPacket::StartLiveGame {
+                            user_name,
+                            slot_count,
+                        } => {
+                            if let Some(id) = live_game.take() {
+                                end_live_game(&live_games, id);
+                            }
+                            live_game = Some(start_live_game(&live_games, user_name, slot_count));
+                        }
+                        ClientPacket::LiveEvents(events) => {
+                            if let Some(id) = live_game {
+                                relay_live_events(&live_games, id, events);
+                            }
+                        }
+                        ClientPacket::EndLiveGame => {
+                            if let Some(id) = live_game.take() {
+                                end_live_game(&live_games, id);
+                            }
+                        }
+                        ClientPacket::GetLiveGames => {
+                            if let Err(err) =
+                                writer.send(&ServerPacket::LiveGames(list_live_games(&live_games)))
+                            {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::Spectate(id) => {
+                            if let Some(old_id) = spectating.take() {
+                                stop_spectating(&live_games, old_id, writer.addr());
+                            }
+                            // the reply to a game that can be spectated is sent by spectate itself
+                            let result = match spectate(&live_games, id, &writer) {
+                                Ok(true) => {
+                                    spectating = Some(id);
+                                    Ok(())
+                                }
+                                Ok(false) => writer.send(&ServerPacket::ErrorState),
+                                Err(err) => Err(err),
+                            };
+                            if let Err(err) = result {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::StopSpectating => {
+                            if let Some(id) = spectating.take() {
+                                stop_spectating(&live_games, id, writer.addr());
+                            }
+                        }
+                        ClientPacket::GetDailyChallenge => {
+                            if let Err(err) =
+                                writer.send(&ServerPacket::DailyChallenge(daily_challenge()))
+                            {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::StartDailyAttempt(packet) => {
+                            let response = {
+                                let mut lock = client.lock().unwrap();
+                                start_daily_attempt(&mut lock, &packet)
+                            };
+                            if let ServerPacket::DailyAttemptStarted(true) = response {
+                                daily_attempt = Some(daily_key(packet.day, &packet.login_info));
+                            }
+                            if let Err(err) = writer.send(&response) {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::SubmitDailyScore(packet) => {
+                            let key = daily_key(packet.day, &packet.data.login_info);
+                            let owns_attempt = daily_attempt.as_ref() == Some(&key);
+                            if owns_attempt {
+                                daily_attempt = None;
+                            }
+                            let response = {
+                                let mut lock = client.lock().unwrap();
+                                submit_daily_score(&mut lock, packet, owns_attempt)
+                            };
+                            if let Err(err) = writer.send(&response) {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::SubmitCategoryScore(packet) => {
+                            let response = {
+                                let mut lock = client.lock().unwrap();
+                                submit_category_score(&mut lock, packet)
+                            };
+                            if let Err(err) = writer.send(&response) {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::GetCategoryLeaderBoard(category) => {
+                            let response = {
+                                let mut lock = client.lock().unwrap();
+                                match category_leader_board(&mut lock, &category) {
+                                    None => ServerPacket::ErrorState,
+                                    Some(list) => ServerPacket::LeaderBoard(list),
+                                }
+                            };
+                            if let Err(err) = writer.send(&response) {
+                                println!("{}", err);
+                                break 'connection;
+                            }
+                        }
+                        ClientPacket::GetDailyLeaderBoard => {
+                            let response = {
+                                let mut lock = client.lock().unwrap();
+                                match daily_leader_board(&mut lock, daily_challenge().day) {
+                                    None => ServerPacket::ErrorState,
+                                    Some(list) => ServerPacket::LeaderBoard(list),
+                                }
+                            };
+                            if let Err(err) = writer.send(&response) {
+                                println!("{}", err);
+                                break 'connection;
+                            }
                         }
                     }
                 }
@@ -282,4 +483,13 @@ fn handle_client(mut stream: TcpStream, client: Arc<Mutex<SmolDbClient>>) {
             }
         }
     }
+
+    // forfeit any versus match the client was in when they disconnect, and stop any live game they were in
+    leave_versus(&versus_queue, &versus_slot, &writer);
+    if let Some(id) = live_game {
+        end_live_game(&live_games, id);
+    }
+    if let Some(id) = spectating {
+        stop_spectating(&live_games, id, writer.addr());
+    }
 }
@@ -0,0 +1,218 @@
+use crate::writer::PacketWriter;
+use cr_tile_game_common::packet::ServerPacket;
+use cr_tile_game_common::spectate::{LiveEvent, LiveGameInfo};
+use cr_tile_game_common::tile::TileKind;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Every game currently being played that can be spectated, keyed by the id of the game
+pub type LiveGames = Arc<Mutex<HashMap<u64, LiveGame>>>;
+
+/// The id given to the next live game
+static NEXT_GAME_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A game being played, along with everyone watching it
+pub struct LiveGame {
+    info: LiveGameInfo,
+    spectators: Vec<PacketWriter>,
+    /// Every tile on the field of the player, so spectators who join late can be sent them
+    tiles: Vec<FieldTile>,
+    /// The latest status of the player, along with the tick their game was on when they sent it
+    score: i32,
+    lives: i32,
+    tick: u64,
+}
+
+/// A tile on the field of a live game
+struct FieldTile {
+    id: u32,
+    slot: u8,
+    speed: f32,
+    kind: TileKind,
+    /// The tick of the game of the player that the tile was spawned by
+    spawn_tick: u64,
+}
+
+impl LiveGame {
+    /// Keeps track of the tiles on the field and the status of the player from the events of the game.
+    /// Tiles are counted as spawned on the tick of the status the player sends along with them
+    fn record(&mut self, events: &[LiveEvent]) {
+        let tick = events
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                LiveEvent::Status { tick, .. } => Some(*tick),
+                _ => None,
+            })
+            .unwrap_or(self.tick);
+        for event in events {
+            match *event {
+                LiveEvent::TileSpawned {
+                    id,
+                    slot,
+                    speed,
+                    kind,
+                    ..
+                } => self.tiles.push(FieldTile {
+                    id,
+                    slot,
+                    speed,
+                    kind,
+                    spawn_tick: tick,
+                }),
+                LiveEvent::TileHit { id } | LiveEvent::TileMissed { id } => {
+                    self.tiles.retain(|tile| tile.id != id);
+                }
+                LiveEvent::Status { score, lives, tick } => {
+                    self.score = score;
+                    self.lives = lives;
+                    self.tick = tick;
+                }
+                LiveEvent::SlotPressed { .. } => {}
+            }
+        }
+    }
+
+    /// Returns the events that bring a new spectator up to where the game is now
+    fn snapshot(&self) -> Vec<LiveEvent> {
+        let mut events = self
+            .tiles
+            .iter()
+            .map(|tile| LiveEvent::TileSpawned {
+                id: tile.id,
+                slot: tile.slot,
+                speed: tile.speed,
+                kind: tile.kind,
+                ticks: self.tick.saturating_sub(tile.spawn_tick),
+            })
+            .collect::<Vec<_>>();
+        events.push(LiveEvent::Status {
+            score: self.score,
+            lives: self.lives,
+            tick: self.tick,
+        });
+        events
+    }
+}
+
+/// Registers a new live game, and returns its id
+pub fn start_live_game(games: &LiveGames, user_name: String, slot_count: u8) -> u64 {
+    let id = NEXT_GAME_ID.fetch_add(1, Ordering::Relaxed);
+    games.lock().unwrap().insert(
+        id,
+        LiveGame {
+            info: LiveGameInfo {
+                id,
+                user_name,
+                slot_count,
+            },
+            spectators: vec![],
+            tiles: vec![],
+            score: 0,
+            lives: 0,
+            tick: 0,
+        },
+    );
+    id
+}
+
+/// Sends the events to every spectator of the game, dropping spectators that can no longer be written to
+pub fn relay_live_events(games: &LiveGames, id: u64, events: Vec<LiveEvent>) {
+    if let Some(game) = games.lock().unwrap().get_mut(&id) {
+        game.record(&events);
+        let packet = ServerPacket::LiveEvents(events);
+        game.spectators
+            .retain(|spectator| spectator.send(&packet).is_ok());
+    }
+}
+
+/// Removes the game, and lets every spectator know that it is over
+pub fn end_live_game(games: &LiveGames, id: u64) {
+    if let Some(game) = games.lock().unwrap().remove(&id) {
+        for spectator in game.spectators.iter() {
+            let _ = spectator.send(&ServerPacket::LiveGameEnded);
+        }
+    }
+}
+
+/// Returns every game that can currently be spectated
+pub fn list_live_games(games: &LiveGames) -> Vec<LiveGameInfo> {
+    games
+        .lock()
+        .unwrap()
+        .values()
+        .map(|game| game.info.clone())
+        .collect()
+}
+
+/// Sends the info of the game to the spectator, along with the tiles already on the field, then adds them as a spectator of the game.
+/// The games are locked the whole time, so no live events can reach the spectator before the info does.
+/// Returns false if the game does not exist
+pub fn spectate(games: &LiveGames, id: u64, spectator: &PacketWriter) -> std::io::Result<bool> {
+    let mut lock = games.lock().unwrap();
+    let Some(game) = lock.get_mut(&id) else {
+        return Ok(false);
+    };
+    spectator.send(&ServerPacket::SpectateStarted(game.info.clone()))?;
+    spectator.send(&ServerPacket::LiveEvents(game.snapshot()))?;
+    game.spectators.push(spectator.clone());
+    Ok(true)
+}
+
+/// Removes the player with the given address from the spectators of the game
+pub fn stop_spectating(games: &LiveGames, id: u64, addr: SocketAddr) {
+    if let Some(game) = games.lock().unwrap().get_mut(&id) {
+        game.spectators.retain(|spectator| spectator.addr() != addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The event of a normal tile spawning
+    fn spawned(id: u32) -> LiveEvent {
+        LiveEvent::TileSpawned {
+            id,
+            slot: 0,
+            speed: 2.0,
+            kind: TileKind::Normal,
+            ticks: 0,
+        }
+    }
+
+    /// The status the player sends every frame
+    fn status(tick: u64) -> LiveEvent {
+        LiveEvent::Status {
+            score: 3,
+            lives: 9,
+            tick,
+        }
+    }
+
+    #[test]
+    fn a_snapshot_has_the_tiles_on_the_field_moved_to_where_they_are() {
+        let games: LiveGames = Arc::new(Mutex::new(HashMap::new()));
+        let id = start_live_game(&games, "Player".to_string(), 3);
+        relay_live_events(&games, id, vec![spawned(0), status(10)]);
+        relay_live_events(&games, id, vec![spawned(1), status(40)]);
+        relay_live_events(&games, id, vec![LiveEvent::TileHit { id: 0 }, status(100)]);
+
+        let snapshot = games.lock().unwrap()[&id].snapshot();
+        assert_eq!(
+            snapshot,
+            vec![
+                LiveEvent::TileSpawned {
+                    id: 1,
+                    slot: 0,
+                    speed: 2.0,
+                    kind: TileKind::Normal,
+                    ticks: 60,
+                },
+                status(100),
+            ]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::writer::PacketWriter;
+use cr_tile_game_common::packet::{ServerPacket, VersusJoin, VersusMatch, VersusStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The player waiting in the versus queue, if there is one
+pub type VersusQueue = Arc<Mutex<Option<WaitingPlayer>>>;
+
+/// The opponent a connection is matched against, filled in by whichever thread makes the match
+pub type VersusSlot = Arc<Mutex<Option<VersusOpponent>>>;
+
+/// A player waiting for someone to play against
+pub struct WaitingPlayer {
+    name: String,
+    slot_count: u8,
+    /// The connection of the player, along with the address used to tell who is waiting
+    writer: PacketWriter,
+    /// The slot of the waiting player, the thread that matches with them places itself here
+    slot: VersusSlot,
+}
+
+/// The other player in a match, as seen by one of the two connection threads
+pub struct VersusOpponent {
+    writer: PacketWriter,
+    /// Shared by both players in the match, set once a winner has been declared
+    finished: Arc<AtomicBool>,
+}
+
+/// Creates a seed for a new match
+fn new_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Matches the player with the player waiting in the queue, or places them in the queue if nobody is waiting.
+pub fn join_versus(
+    queue: &VersusQueue,
+    slot: &VersusSlot,
+    writer: &PacketWriter,
+    join: VersusJoin,
+) {
+    let name = join.login_info.user_name;
+    let mut lock = queue.lock().unwrap();
+
+    match lock.take() {
+        Some(waiting) if waiting.writer.addr() != writer.addr() => {
+            let seed = new_seed();
+            let finished = Arc::new(AtomicBool::new(false));
+
+            let found = ServerPacket::VersusMatchFound(VersusMatch {
+                seed,
+                slot_count: waiting.slot_count,
+                opponent: name.clone(),
+            });
+            if waiting.writer.send(&found).is_err() {
+                // the waiting player left without telling us, so take their place in the queue instead
+                println!("Waiting versus player disconnected, queueing {}", name);
+                *lock = Some(WaitingPlayer {
+                    name,
+                    slot_count: join.slot_count,
+                    writer: writer.clone(),
+                    slot: slot.clone(),
+                });
+                return;
+            }
+
+            *waiting.slot.lock().unwrap() = Some(VersusOpponent {
+                writer: writer.clone(),
+                finished: finished.clone(),
+            });
+            *slot.lock().unwrap() = Some(VersusOpponent {
+                writer: waiting.writer,
+                finished,
+            });
+
+            let _ = writer.send(&ServerPacket::VersusMatchFound(VersusMatch {
+                seed,
+                slot_count: waiting.slot_count,
+                opponent: waiting.name.clone(),
+            }));
+            println!("Versus match started: {} vs {}", waiting.name, name);
+        }
+        _ => {
+            *lock = Some(WaitingPlayer {
+                name,
+                slot_count: join.slot_count,
+                writer: writer.clone(),
+                slot: slot.clone(),
+            });
+        }
+    }
+}
+
+/// Relays the status of the player to their opponent, and declares the opponent the winner once the player runs out of lives.
+pub fn relay_versus_update(slot: &VersusSlot, writer: &PacketWriter, status: VersusStatus) {
+    let mut lock = slot.lock().unwrap();
+
+    let finished = match lock.as_mut() {
+        None => return,
+        Some(opponent) => {
+            if opponent.finished.load(Ordering::SeqCst) {
+                true
+            } else {
+                let _ = opponent
+                    .writer
+                    .send(&ServerPacket::VersusOpponentUpdate(status));
+
+                if status.lives < 0 && !opponent.finished.swap(true, Ordering::SeqCst) {
+                    let _ = opponent
+                        .writer
+                        .send(&ServerPacket::VersusGameOver { won: true });
+                    let _ = writer.send(&ServerPacket::VersusGameOver { won: false });
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    };
+
+    if finished {
+        *lock = None;
+    }
+}
+
+/// Removes the player from the queue, and forfeits any match they are in.
+pub fn leave_versus(queue: &VersusQueue, slot: &VersusSlot, writer: &PacketWriter) {
+    let mut lock = queue.lock().unwrap();
+    if lock
+        .as_ref()
+        .is_some_and(|waiting| waiting.writer.addr() == writer.addr())
+    {
+        *lock = None;
+    }
+    drop(lock);
+
+    if let Some(opponent) = slot.lock().unwrap().take() {
+        if !opponent.finished.swap(true, Ordering::SeqCst) {
+            let _ = opponent
+                .writer
+                .send(&ServerPacket::VersusGameOver { won: true });
+        }
+    }
+}
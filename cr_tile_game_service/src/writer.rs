@@ -0,0 +1,83 @@
+use cr_tile_game_common::packet::ServerPacket;
+use std::io;
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// The sending half of the connection to a player, shared by every thread that sends packets to them.
+/// Each packet is written whole while the stream is locked, so packets sent from different threads never interleave
+#[derive(Clone)]
+pub struct PacketWriter {
+    /// The address of the player, used to tell connections apart
+    addr: SocketAddr,
+    stream: Arc<Mutex<TcpStream>>,
+}
+
+impl PacketWriter {
+    /// Creates the writer of the connection, the stream is cloned so the connection can keep reading from it
+    pub fn new(stream: &TcpStream) -> io::Result<Self> {
+        Ok(Self {
+            addr: stream.peer_addr()?,
+            stream: Arc::new(Mutex::new(stream.try_clone()?)),
+        })
+    }
+
+    /// Returns the address of the player
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Writes a packet to the player
+    pub fn send(&self, packet: &ServerPacket) -> io::Result<()> {
+        let ser = serde_json::to_string(packet).unwrap();
+        self.stream.lock().unwrap().write_all(ser.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cr_tile_game_common::packet::drain_packets;
+    use cr_tile_game_common::spectate::LiveEvent;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn packets_sent_from_several_threads_arrive_whole() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut player = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (connection, _) = listener.accept().unwrap();
+        let writer = PacketWriter::new(&connection).unwrap();
+
+        // packets larger than a single write, so unsynchronized writes would be likely to interleave
+        let packet = ServerPacket::LiveEvents(vec![LiveEvent::SlotPressed { slot: 1 }; 2000]);
+        let threads = (0..4)
+            .map(|_| {
+                let writer = writer.clone();
+                let packet = packet.clone();
+                thread::spawn(move || {
+                    for _ in 0..25 {
+                        writer.send(&packet).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut received = 0;
+        let mut pending = vec![];
+        let mut buf = [0; 4096];
+        while received < 100 {
+            let read_length = player.read(&mut buf).unwrap();
+            pending.extend_from_slice(&buf[0..read_length]);
+            for packet in drain_packets::<ServerPacket>(&mut pending) {
+                assert!(matches!(packet, ServerPacket::LiveEvents(events) if events.len() == 2000));
+                received += 1;
+            }
+        }
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,394 @@
+use crate::BASE_SPEED;
+use cr_tile_game_common::chart::{Chart, ChartNote, MAX_SLOT_COUNT};
+use cr_tile_game_common::tile::TileKind;
+use hound::{SampleFormat, WavReader};
+use std::io::Cursor;
+
+/// The number of samples the energy of the song is measured over
+const FRAME_SIZE: usize = 1024;
+/// The number of samples between the start of each frame
+const HOP_SIZE: usize = 512;
+/// The slowest tempo that can be detected, in beats per minute
+const MIN_BPM: f32 = 60.0;
+/// The fastest tempo that can be detected, in beats per minute
+const MAX_BPM: f32 = 200.0;
+/// The tempo detection leans towards, so a song is not detected at double or half its tempo
+const PREFERRED_BPM: f32 = 120.0;
+/// The number of frames on each side of an onset it has to be the strongest of
+const PEAK_FRAMES: usize = 3;
+/// The number of frames on each side of an onset that its strength is compared with
+const THRESHOLD_FRAMES: usize = 16;
+/// How much stronger than the frames around it an onset has to be
+const THRESHOLD_RATIO: f32 = 1.5;
+/// The part of a beat that each beat can be moved by to line up with the song
+const BEAT_SEARCH: f32 = 0.1;
+/// The part of the loudest frame a frame has to reach to not count as silence
+const SILENCE_RATIO: f32 = 0.05;
+
+#[derive(Debug)]
+pub enum BeatError {
+    /// The file is not a wav file that can be read
+    ParseError(hound::Error),
+    /// The slot count asked for is 0 or more than the game supports
+    InvalidSlotCount(u8),
+    /// The samples of the file are integers with no bits or more than 32 bits, which can not be read
+    InvalidBitDepth(u16),
+    /// The song is too short or too quiet for a tempo to be found
+    NoBeats,
+}
+
+/// How many tiles are made from the song
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Density {
+    /// A tile every other beat
+    Low,
+    /// A tile every beat
+    Medium,
+    /// A tile for every onset, as long as it is at least a quarter of a beat after the last tile
+    High,
+}
+
+/// The settings used when making a chart from a song
+#[derive(Debug, Clone)]
+pub struct BeatOptions {
+    /// The number of slots the chart is played with
+    pub slot_count: u8,
+    /// How many tiles are made
+    pub density: Density,
+    /// The speed the tiles fall at
+    pub speed: f32,
+}
+
+impl Default for BeatOptions {
+    fn default() -> Self {
+        Self {
+            slot_count: 4,
+            density: Density::Medium,
+            speed: BASE_SPEED,
+        }
+    }
+}
+
+/// Reads every sample of the wav file with every channel mixed together, along with the sample rate
+fn read_samples(bytes: &[u8]) -> Result<(Vec<f32>, u32), BeatError> {
+    let mut reader = WavReader::new(Cursor::new(bytes)).map_err(BeatError::ParseError)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(BeatError::ParseError)?,
+        SampleFormat::Int => {
+            // the samples are read as 32 bit integers, and a depth of 0 can not be scaled
+            if !(1..=32).contains(&spec.bits_per_sample) {
+                return Err(BeatError::InvalidBitDepth(spec.bits_per_sample));
+            }
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(BeatError::ParseError)?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+/// The loudness of the song over time, and how suddenly it gets louder, measured once a frame
+struct Envelope {
+    /// The number of frames in a second
+    frame_rate: f32,
+    /// The energy of each frame
+    energy: Vec<f32>,
+    /// How much louder each frame is than the frame before it
+    onset_strength: Vec<f32>,
+    /// The energy a frame has to be louder than to not count as silence
+    silence: f32,
+}
+
+impl Envelope {
+    /// Measures the song a frame at a time
+    fn new(samples: &[f32], sample_rate: u32) -> Self {
+        let frame_count = samples.len().saturating_sub(FRAME_SIZE) / HOP_SIZE;
+        let energy: Vec<f32> = (0..frame_count)
+            .map(|frame| {
+                let start = frame * HOP_SIZE;
+                samples[start..start + FRAME_SIZE]
+                    .iter()
+                    .map(|sample| sample * sample)
+                    .sum::<f32>()
+            })
+            .collect();
+        // comparing the log of the energy makes an onset in a quiet part count as much as one in a loud part
+        let onset_strength = (0..energy.len())
+            .map(|frame| match frame.checked_sub(1) {
+                None => 0.0,
+                Some(last) => (energy[frame].ln_1p() - energy[last].ln_1p()).max(0.0),
+            })
+            .collect();
+        let silence = energy.iter().copied().fold(0.0, f32::max) * SILENCE_RATIO;
+        Self {
+            frame_rate: sample_rate as f32 / HOP_SIZE as f32,
+            energy,
+            onset_strength,
+            silence,
+        }
+    }
+
+    /// Returns the time in seconds of the middle of the frame
+    fn time_of(&self, frame: usize) -> f32 {
+        (frame as f32 + (FRAME_SIZE / HOP_SIZE) as f32 / 2.0) / self.frame_rate
+    }
+
+    /// Returns true if the frame is loud enough to not be silence
+    fn is_audible(&self, frame: usize) -> bool {
+        self.energy
+            .get(frame)
+            .is_some_and(|energy| *energy > self.silence)
+    }
+
+    /// Returns every frame that starts a note or a hit, the frames that are much louder than the frames around them
+    fn onsets(&self) -> Vec<usize> {
+        let strength = &self.onset_strength;
+        (0..strength.len())
+            .filter(|frame| {
+                let around = |range: usize| {
+                    let start = frame.saturating_sub(range);
+                    let end = (frame + range + 1).min(strength.len());
+                    &strength[start..end]
+                };
+                let is_peak = around(PEAK_FRAMES)
+                    .iter()
+                    .all(|other| *other <= strength[*frame]);
+                let nearby = around(THRESHOLD_FRAMES);
+                let mean = nearby.iter().sum::<f32>() / nearby.len() as f32;
+                is_peak && strength[*frame] > mean * THRESHOLD_RATIO && self.is_audible(*frame)
+            })
+            .collect()
+    }
+
+    /// Returns the length of a beat in frames, found from how often the onset strength repeats itself
+    fn beat_length(&self) -> Option<usize> {
+        let strength = &self.onset_strength;
+        let mean = strength.iter().sum::<f32>() / strength.len().max(1) as f32;
+        let centered: Vec<f32> = strength.iter().map(|value| value - mean).collect();
+        let shortest = (self.frame_rate * 60.0 / MAX_BPM).floor() as usize;
+        let longest = (self.frame_rate * 60.0 / MIN_BPM).ceil() as usize;
+        if centered.len() <= longest * 2 {
+            return None;
+        }
+
+        (shortest.max(1)..=longest)
+            .map(|lag| {
+                let correlation: f32 = centered
+                    .iter()
+                    .zip(&centered[lag..])
+                    .map(|(a, b)| a * b)
+                    .sum();
+                // tempos far from the preferred tempo are weighted down, by how many octaves away they are
+                let bpm = self.frame_rate * 60.0 / lag as f32;
+                let octaves = (bpm / PREFERRED_BPM).log2();
+                (lag, correlation * (-octaves * octaves).exp())
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .filter(|(_, correlation)| *correlation > 0.0)
+            .map(|(lag, _)| lag)
+    }
+
+    /// Returns the frame of every beat of the song, each moved a little to line up with the strongest onset near it
+    fn beats(&self, beat_length: usize) -> Vec<usize> {
+        let strength = &self.onset_strength;
+        // the first beat is where beats a whole beat apart line up with the most onsets
+        let first = (0..beat_length)
+            .max_by(|a, b| {
+                let score = |start: &usize| -> f32 {
+                    strength.iter().skip(*start).step_by(beat_length).sum()
+                };
+                score(a).total_cmp(&score(b))
+            })
+            .unwrap_or(0);
+
+        let search = ((beat_length as f32 * BEAT_SEARCH).round() as usize).max(1);
+        let mut beats = vec![];
+        let mut expected = first;
+        while expected < strength.len() {
+            let start = expected.saturating_sub(search);
+            let end = (expected + search + 1).min(strength.len());
+            let beat = (start..end)
+                .max_by(|a, b| strength[*a].total_cmp(&strength[*b]))
+                .unwrap_or(expected);
+            beats.push(beat);
+            expected = beat + beat_length;
+        }
+        beats
+    }
+}
+
+/// Makes a chart from a wav file, by finding its tempo, beats and onsets.
+/// Tiles are placed on the beats or the onsets depending on the density, and never where the song is silent.
+pub fn generate_chart(bytes: &[u8], options: &BeatOptions) -> Result<Chart, BeatError> {
+    if options.slot_count == 0 || options.slot_count > MAX_SLOT_COUNT {
+        return Err(BeatError::InvalidSlotCount(options.slot_count));
+    }
+    let (samples, sample_rate) = read_samples(bytes)?;
+    let envelope = Envelope::new(&samples, sample_rate);
+    let beat_length = envelope.beat_length().ok_or(BeatError::NoBeats)?;
+    let beats = envelope.beats(beat_length);
+    // the beats are moved to line up with the song, so the time between them gives a more exact tempo than the beat length
+    let bpm = match (beats.first(), beats.last()) {
+        (Some(first), Some(last)) if last > first => {
+            let frames_per_beat = (last - first) as f32 / (beats.len() - 1) as f32;
+            envelope.frame_rate * 60.0 / frames_per_beat
+        }
+        _ => envelope.frame_rate * 60.0 / beat_length as f32,
+    };
+
+    let frames: Vec<usize> = match options.density {
+        Density::Low => beats.into_iter().step_by(2).collect(),
+        Density::Medium => beats,
+        Density::High => {
+            let min_gap = beat_length / 4;
+            let mut frames: Vec<usize> = vec![];
+            for onset in envelope.onsets() {
+                match frames.last() {
+                    Some(last) if onset - last < min_gap => {}
+                    _ => frames.push(onset),
+                }
+            }
+            frames
+        }
+    };
+
+    let mut slot = 0;
+    let notes: Vec<ChartNote> = frames
+        .into_iter()
+        .filter(|frame| envelope.is_audible(*frame))
+        .map(|frame| {
+            // the strength of the onset picks how far the next tile moves, so the pattern follows the song
+            if options.slot_count > 1 {
+                let step = (envelope.onset_strength[frame] * 10.0) as u8 % (options.slot_count - 1);
+                slot = (slot + 1 + step) % options.slot_count;
+            }
+            ChartNote {
+                time: envelope.time_of(frame),
+                slot,
+                kind: TileKind::Normal,
+                speed: options.speed,
+            }
+        })
+        .collect();
+    if notes.is_empty() {
+        return Err(BeatError::NoBeats);
+    }
+
+    Ok(Chart {
+        title: "Generated chart".to_string(),
+        author: String::new(),
+        slot_count: options.slot_count,
+        audio: None,
+        bpm: Some((bpm * 10.0).round() / 10.0),
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{WavSpec, WavWriter};
+
+    /// The sample rate of the test songs
+    const SAMPLE_RATE: u32 = 44100;
+    /// The tempo of the click track
+    const BPM: f32 = 120.0;
+    /// The number of beats in the click track
+    const BEATS: usize = 32;
+
+    /// Returns a 16 bit wav file of a click on every beat, each a short burst of a tone that fades out
+    fn click_track() -> Vec<u8> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let beat_samples = (SAMPLE_RATE as f32 * 60.0 / BPM) as usize;
+        let click_samples = SAMPLE_RATE as usize / 50;
+        let mut bytes = Cursor::new(vec![]);
+        let mut writer = WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in 0..beat_samples * BEATS {
+            let into_beat = sample % beat_samples;
+            let value = if into_beat < click_samples {
+                let fade = 1.0 - into_beat as f32 / click_samples as f32;
+                let phase = into_beat as f32 / SAMPLE_RATE as f32 * 1000.0;
+                (phase * std::f32::consts::TAU).sin() * fade * 0.8
+            } else {
+                0.0
+            };
+            writer
+                .write_sample((value * i16::MAX as f32) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    /// Returns the chart made from the click track with the given density
+    fn click_chart(density: Density) -> Chart {
+        let options = BeatOptions {
+            density,
+            ..BeatOptions::default()
+        };
+        generate_chart(&click_track(), &options).unwrap()
+    }
+
+    #[test]
+    fn the_tempo_of_a_click_track_is_found() {
+        let bpm = click_chart(Density::Medium).bpm.unwrap();
+        assert!((bpm - BPM).abs() < 1.0, "found {} bpm", bpm);
+    }
+
+    #[test]
+    fn the_density_sets_how_many_tiles_there_are() {
+        let medium = click_chart(Density::Medium).notes.len();
+        assert!(medium.abs_diff(BEATS) <= 1, "{} tiles", medium);
+        let low = click_chart(Density::Low).notes.len();
+        assert!(low.abs_diff(BEATS / 2) <= 1, "{} tiles", low);
+        let high = click_chart(Density::High).notes.len();
+        assert!(high.abs_diff(BEATS) <= 1, "{} tiles", high);
+    }
+
+    #[test]
+    fn samples_wider_than_32_bits_are_rejected() {
+        // an extensible wav file that stores 16 bit samples, but says 40 of their bits are used
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(4 + 48 + 8 + 4_u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&40_u32.to_le_bytes());
+        bytes.extend_from_slice(&0xfffe_u16.to_le_bytes());
+        bytes.extend_from_slice(&1_u16.to_le_bytes());
+        bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        bytes.extend_from_slice(&2_u16.to_le_bytes());
+        bytes.extend_from_slice(&16_u16.to_le_bytes());
+        bytes.extend_from_slice(&22_u16.to_le_bytes());
+        bytes.extend_from_slice(&40_u16.to_le_bytes());
+        bytes.extend_from_slice(&0_u32.to_le_bytes());
+        bytes.extend_from_slice(&[
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38,
+            0x9b, 0x71,
+        ]);
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&4_u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        assert!(matches!(
+            generate_chart(&bytes, &BeatOptions::default()),
+            Err(BeatError::InvalidBitDepth(40))
+        ));
+    }
+}
@@ -0,0 +1,7 @@
+pub mod beats;
+pub mod midi;
+pub mod osu;
+pub mod sm;
+
+/// The speed converted tiles fall at, unless the chart being converted changes it
+pub const BASE_SPEED: f32 = 4.0;
@@ -0,0 +1,233 @@
+use cr_tile_game_common::chart::Chart;
+use cr_tile_game_tools::beats::{generate_chart, BeatOptions, Density};
+use cr_tile_game_tools::midi::{convert_midi, LaneMode, MidiOptions};
+use cr_tile_game_tools::osu::convert_osu;
+use cr_tile_game_tools::sm::convert_sm;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+/// The text printed when the arguments can not be understood
+const USAGE: &str = "usage: cr_tile_game_tools <format> <input file> [output file] [--option value]...
+formats:
+  osu    an osu!mania beatmap (.osu) with 1 to 5 keys
+  sm     a StepMania simfile (.sm or .ssc), every difficulty with 1 to 5 panels is converted
+  midi   a midi file (.mid), every note that starts playing becomes a tile
+  wav    a song (.wav), tiles are placed on the beats and onsets that are found in it
+midi options:
+  --slots <1-5>              the number of slots of the chart, 4 by default
+  --lanes <pitch|channel>    split the pitches used across the slots, or give each channel a slot, pitch by default
+  --channels <1,2,...>       only use notes from these channels, every channel is used by default
+  --gap <seconds>            leave out notes closer than this to the last tile, 0.1 by default
+  --chord <count>            the most tiles that reach the bar together, 1 by default
+  --speed <speed>            the speed tiles fall at, 4 by default
+wav options:
+  --slots <1-5>              the number of slots of the chart, 4 by default
+  --density <low|medium|high>  a tile every other beat, every beat, or every onset, medium by default
+  --speed <speed>            the speed tiles fall at, 4 by default
+the chart is written next to the input file when no output file is given,
+when there is more than one chart the name of each is added to the file name";
+
+/// Prints the message along with the usage, and exits
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    exit(1);
+}
+
+/// Parses the value of an option, or returns an error naming the option
+fn parse_option<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for --{}: {}", name, value))
+}
+
+/// Reads the options of the midi converter
+fn midi_options(options: &[(String, String)]) -> Result<MidiOptions, String> {
+    let mut midi_options = MidiOptions::default();
+    for (name, value) in options {
+        match name.as_str() {
+            "slots" => midi_options.slot_count = parse_option(name, value)?,
+            "lanes" => {
+                midi_options.lanes = match value.as_str() {
+                    "pitch" => LaneMode::Pitch,
+                    "channel" => LaneMode::Channel,
+                    _ => return Err(format!("Invalid value for --lanes: {}", value)),
+                }
+            }
+            "channels" => {
+                // channels are numbered from 1 by most music software
+                midi_options.channels = value
+                    .split(',')
+                    .map(|channel| match parse_option::<u8>(name, channel.trim())? {
+                        channel @ 1..=16 => Ok(channel - 1),
+                        _ => Err(format!("Invalid value for --channels: {}", value)),
+                    })
+                    .collect::<Result<Vec<u8>, String>>()?;
+            }
+            "gap" => midi_options.min_gap = parse_option(name, value)?,
+            "chord" => midi_options.max_chord = parse_option(name, value)?,
+            "speed" => midi_options.speed = parse_option(name, value)?,
+            _ => return Err(format!("Unknown option for midi: --{}", name)),
+        }
+    }
+    Ok(midi_options)
+}
+
+/// Reads the options of the chart generator
+fn beat_options(options: &[(String, String)]) -> Result<BeatOptions, String> {
+    let mut beat_options = BeatOptions::default();
+    for (name, value) in options {
+        match name.as_str() {
+            "slots" => beat_options.slot_count = parse_option(name, value)?,
+            "density" => {
+                beat_options.density = match value.as_str() {
+                    "low" => Density::Low,
+                    "medium" => Density::Medium,
+                    "high" => Density::High,
+                    _ => return Err(format!("Invalid value for --density: {}", value)),
+                }
+            }
+            "speed" => beat_options.speed = parse_option(name, value)?,
+            _ => return Err(format!("Unknown option for wav: --{}", name)),
+        }
+    }
+    Ok(beat_options)
+}
+
+/// Converts the contents of the input file to charts using the converter for the given format, along with a name for each chart when there can be more than one
+fn convert(
+    format: &str,
+    input: &Path,
+    options: &[(String, String)],
+) -> Result<Vec<(String, Chart)>, String> {
+    let read_error = |err: std::io::Error| format!("Failed to read {}: {}", input.display(), err);
+    if !matches!(format, "midi" | "wav") {
+        if let Some((name, _)) = options.first() {
+            return Err(format!("Unknown option for {}: --{}", format, name));
+        }
+    }
+    match format {
+        "osu" => {
+            let source = fs::read_to_string(input).map_err(read_error)?;
+            convert_osu(&source)
+                .map(|chart| vec![(String::new(), chart)])
+                .map_err(|err| format!("Failed to convert beatmap: {:?}", err))
+        }
+        "sm" => {
+            let source = fs::read_to_string(input).map_err(read_error)?;
+            convert_sm(&source).map_err(|err| format!("Failed to convert simfile: {:?}", err))
+        }
+        "midi" => {
+            let bytes = fs::read(input).map_err(read_error)?;
+            let options = midi_options(options)?;
+            convert_midi(&bytes, &options)
+                .map(|chart| vec![(String::new(), chart)])
+                .map_err(|err| format!("Failed to convert midi file: {:?}", err))
+        }
+        "wav" => {
+            let bytes = fs::read(input).map_err(read_error)?;
+            let options = beat_options(options)?;
+            let mut chart = generate_chart(&bytes, &options)
+                .map_err(|err| format!("Failed to generate chart: {:?}", err))?;
+            // the song is played from next to the chart, so the chart has to be written to the same folder
+            chart.title = input
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            chart.audio = input
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string());
+            Ok(vec![(String::new(), chart)])
+        }
+        _ => Err(format!("Unknown format: {}", format)),
+    }
+}
+
+/// Returns the path a chart is written to, which has the name of the chart added when there is more than one chart
+fn chart_path(output: &Path, name: &str, index: usize, chart_count: usize) -> PathBuf {
+    if chart_count == 1 {
+        return output.to_path_buf();
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    // the index keeps charts with the same name from overwriting each other
+    output.with_file_name(format!("{}_{}_{}.json", stem, index + 1, name))
+}
+
+/// Prints a note if the song of the chart can not be played by the game
+fn check_audio(chart: &Chart) {
+    if let Some(audio) = &chart.audio {
+        let extension = Path::new(audio)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        if !matches!(extension.as_deref(), Some("wav") | Some("ogg")) {
+            println!(
+                "The song {} has to be converted to wav or ogg, and the audio field changed to match, before it can be played",
+                audio
+            );
+        }
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut positional = vec![];
+    let mut options = vec![];
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => match args.next() {
+                Some(value) => options.push((name.to_string(), value)),
+                None => fail(&format!("Missing value for --{}", name)),
+            },
+            None => positional.push(arg),
+        }
+    }
+    let (format, input) = match (positional.first(), positional.get(1)) {
+        (Some(format), Some(input)) => (format.as_str(), PathBuf::from(input)),
+        _ => fail("Missing arguments"),
+    };
+    let output = positional
+        .get(2)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| input.with_extension("json"));
+
+    let charts = match convert(format, &input, &options) {
+        Ok(charts) => charts,
+        Err(err) => fail(&err),
+    };
+    for (_, chart) in &charts {
+        if let Err(err) = chart.validate() {
+            fail(&format!(
+                "Converted chart \"{}\" is not playable: {:?}",
+                chart.title, err
+            ));
+        }
+    }
+    if let Some((_, chart)) = charts.first() {
+        check_audio(chart);
+    }
+
+    for (index, (name, chart)) in charts.iter().enumerate() {
+        let path = chart_path(&output, name, index, charts.len());
+        match fs::write(&path, chart.to_json()) {
+            Ok(_) => {
+                println!(
+                    "Wrote \"{}\" with {} notes in {} slots to {}",
+                    chart.title,
+                    chart.notes.len(),
+                    chart.slot_count,
+                    path.display()
+                );
+            }
+            Err(err) => {
+                fail(&format!("Failed to write {}: {}", path.display(), err));
+            }
+        }
+    }
+}
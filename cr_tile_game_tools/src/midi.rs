@@ -0,0 +1,343 @@
+use crate::BASE_SPEED;
+use cr_tile_game_common::chart::{Chart, ChartNote, MAX_SLOT_COUNT};
+use cr_tile_game_common::tile::TileKind;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+/// The length of a beat in microseconds until the file sets a tempo, which is 120 beats per minute
+const DEFAULT_TEMPO: u32 = 500_000;
+
+#[derive(Debug)]
+pub enum MidiError {
+    /// The file is not a valid midi file
+    ParseError(midly::Error),
+    /// The slot count asked for is 0 or more than the game supports
+    InvalidSlotCount(u8),
+    /// No notes were left after the channels were filtered and the notes thinned
+    NoNotes,
+}
+
+/// How the notes of a midi file are given slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaneMode {
+    /// The range of pitches used is split evenly across the slots, low notes on the left
+    Pitch,
+    /// Each channel gets its own slot, wrapping around when there are more channels than slots
+    Channel,
+}
+
+/// The settings used when turning a midi file into a chart
+#[derive(Debug, Clone)]
+pub struct MidiOptions {
+    /// The number of slots the chart is played with
+    pub slot_count: u8,
+    /// How notes are given slots
+    pub lanes: LaneMode,
+    /// The channels notes are taken from, starting from 0, every channel is used when empty
+    pub channels: Vec<u8>,
+    /// The least seconds between two tiles, notes closer than this to the last tile are left out
+    pub min_gap: f32,
+    /// The most tiles that can reach the bar at the same time, in different slots
+    pub max_chord: usize,
+    /// The speed the tiles fall at
+    pub speed: f32,
+}
+
+impl Default for MidiOptions {
+    fn default() -> Self {
+        Self {
+            slot_count: 4,
+            lanes: LaneMode::Pitch,
+            channels: vec![],
+            min_gap: 0.1,
+            max_chord: 1,
+            speed: BASE_SPEED,
+        }
+    }
+}
+
+/// A note that started playing in the midi file
+#[derive(Debug, Clone, Copy)]
+struct MidiNote {
+    /// The tick of the file the note starts on
+    tick: u64,
+    /// The channel the note is played on
+    channel: u8,
+    /// The pitch of the note
+    key: u8,
+}
+
+/// Turns ticks of the file into seconds, following every tempo change
+struct TempoMap {
+    /// The tick each tempo starts on, and the length of a beat in microseconds from then on
+    tempos: Vec<(u64, u32)>,
+    /// The timing of the file, which says how long a tick is
+    timing: Timing,
+}
+
+impl TempoMap {
+    /// Returns the time in seconds of the given tick
+    fn seconds_at(&self, tick: u64) -> f32 {
+        match self.timing {
+            Timing::Timecode(fps, subframes) => tick as f32 / (fps.as_f32() * subframes as f32),
+            Timing::Metrical(ticks_per_beat) => {
+                let ticks_per_beat = ticks_per_beat.as_int().max(1) as f64;
+                let mut seconds = 0.0;
+                let mut last_tick = 0;
+                let mut tempo = DEFAULT_TEMPO;
+                for (change_tick, change_tempo) in &self.tempos {
+                    if *change_tick >= tick {
+                        break;
+                    }
+                    seconds += (change_tick - last_tick) as f64 / ticks_per_beat * tempo as f64;
+                    last_tick = *change_tick;
+                    tempo = *change_tempo;
+                }
+                seconds += (tick - last_tick) as f64 / ticks_per_beat * tempo as f64;
+                (seconds / 1_000_000.0) as f32
+            }
+        }
+    }
+}
+
+/// Turns a midi file into a chart, every note that starts playing becomes a tile.
+/// Notes are thinned out by the least gap between tiles and the most tiles at the same time.
+pub fn convert_midi(bytes: &[u8], options: &MidiOptions) -> Result<Chart, MidiError> {
+    if options.slot_count == 0 || options.slot_count > MAX_SLOT_COUNT {
+        return Err(MidiError::InvalidSlotCount(options.slot_count));
+    }
+    let smf = Smf::parse(bytes).map_err(MidiError::ParseError)?;
+
+    let mut title = None;
+    let mut tempos = vec![];
+    let mut midi_notes = vec![];
+    for track in &smf.tracks {
+        let mut tick = 0;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                // a beat with no length would put every later note on the same time, so the tempo is ignored
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) if tempo.as_int() > 0 => {
+                    tempos.push((tick, tempo.as_int()));
+                }
+                TrackEventKind::Meta(MetaMessage::TrackName(name)) if title.is_none() => {
+                    let name = String::from_utf8_lossy(name).trim().to_string();
+                    if !name.is_empty() {
+                        title = Some(name);
+                    }
+                }
+                // a note on with no velocity is a note off
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } if vel > 0 => {
+                    let channel = channel.as_int();
+                    if options.channels.is_empty() || options.channels.contains(&channel) {
+                        midi_notes.push(MidiNote {
+                            tick,
+                            channel,
+                            key: key.as_int(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    tempos.sort_by_key(|(tick, _)| *tick);
+    midi_notes.sort_by_key(|note| (note.tick, std::cmp::Reverse(note.key)));
+
+    let lowest = midi_notes.iter().map(|note| note.key).min().unwrap_or(0) as usize;
+    let highest = midi_notes.iter().map(|note| note.key).max().unwrap_or(0) as usize;
+    let slot_of = |note: &MidiNote| -> u8 {
+        match options.lanes {
+            LaneMode::Pitch => {
+                let range = highest - lowest + 1;
+                ((note.key as usize - lowest) * options.slot_count as usize / range) as u8
+            }
+            LaneMode::Channel => note.channel % options.slot_count,
+        }
+    };
+
+    let tempo_map = TempoMap {
+        tempos,
+        timing: smf.header.timing,
+    };
+    let mut notes: Vec<ChartNote> = vec![];
+    // the time of the last group of tiles that reach the bar together
+    let mut chord_time = None;
+    for midi_note in &midi_notes {
+        let time = tempo_map.seconds_at(midi_note.tick);
+        let slot = slot_of(midi_note);
+        if chord_time == Some(time) {
+            let chord: Vec<&ChartNote> = notes.iter().filter(|note| note.time == time).collect();
+            // higher notes are added first, so the melody is kept when a chord is cut down
+            if chord.len() >= options.max_chord || chord.iter().any(|note| note.slot == slot) {
+                continue;
+            }
+        } else if chord_time.is_some_and(|last| time - last < options.min_gap) {
+            continue;
+        }
+        chord_time = Some(time);
+        notes.push(ChartNote {
+            time,
+            slot,
+            kind: TileKind::Normal,
+            speed: options.speed,
+        });
+    }
+
+    if notes.is_empty() {
+        return Err(MidiError::NoNotes);
+    }
+    let first_tempo = tempo_map
+        .tempos
+        .first()
+        .map_or(DEFAULT_TEMPO, |(_, tempo)| *tempo);
+
+    Ok(Chart {
+        title: title.unwrap_or_else(|| "Midi chart".to_string()),
+        author: String::new(),
+        slot_count: options.slot_count,
+        audio: None,
+        bpm: Some(60_000_000.0 / first_tempo as f32),
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::num::{u15, u24, u28, u4, u7};
+    use midly::{Format, Header, TrackEvent};
+
+    /// The number of ticks in a beat of the test files, so a beat at the default tempo is 480 ticks long
+    const TICKS_PER_BEAT: u16 = 480;
+
+    /// Returns the bytes of a midi file with a single track of the given events, each after the given number of ticks
+    fn midi_file(events: Vec<(u32, TrackEventKind<'static>)>) -> Vec<u8> {
+        let mut track: Vec<TrackEvent> = events
+            .into_iter()
+            .map(|(delta, kind)| TrackEvent {
+                delta: u28::new(delta),
+                kind,
+            })
+            .collect();
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(TICKS_PER_BEAT)),
+        ));
+        smf.tracks.push(track);
+        let mut bytes = vec![];
+        smf.write_std(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// A note that starts playing the given number of ticks after the last event
+    fn note(delta: u32, channel: u8, key: u8) -> (u32, TrackEventKind<'static>) {
+        (
+            delta,
+            TrackEventKind::Midi {
+                channel: u4::new(channel),
+                message: MidiMessage::NoteOn {
+                    key: u7::new(key),
+                    vel: u7::new(100),
+                },
+            },
+        )
+    }
+
+    /// A tempo change the given number of ticks after the last event, to a beat of the given microseconds
+    fn tempo(delta: u32, tempo: u32) -> (u32, TrackEventKind<'static>) {
+        (
+            delta,
+            TrackEventKind::Meta(MetaMessage::Tempo(u24::new(tempo))),
+        )
+    }
+
+    /// Returns the time of every note of the chart
+    fn times(chart: &Chart) -> Vec<f32> {
+        chart.notes.iter().map(|note| note.time).collect()
+    }
+
+    #[test]
+    fn notes_follow_every_tempo_change() {
+        let bytes = midi_file(vec![
+            tempo(0, 500_000),
+            note(0, 0, 60),
+            note(480, 0, 60),
+            tempo(480, 250_000),
+            note(0, 0, 60),
+            note(480, 0, 60),
+        ]);
+        let chart = convert_midi(&bytes, &MidiOptions::default()).unwrap();
+        assert_eq!(times(&chart), vec![0.0, 0.5, 1.0, 1.25]);
+        assert_eq!(chart.bpm, Some(120.0));
+    }
+
+    #[test]
+    fn a_tempo_of_zero_is_ignored() {
+        let bytes = midi_file(vec![tempo(0, 0), note(480, 0, 60)]);
+        let chart = convert_midi(&bytes, &MidiOptions::default()).unwrap();
+        assert_eq!(times(&chart), vec![0.5]);
+        assert_eq!(chart.bpm, Some(120.0));
+    }
+
+    #[test]
+    fn only_notes_of_the_chosen_channels_are_kept() {
+        let bytes = midi_file(vec![note(0, 0, 60), note(480, 1, 62), note(480, 2, 64)]);
+        let options = MidiOptions {
+            lanes: LaneMode::Channel,
+            channels: vec![1, 2],
+            slot_count: 2,
+            ..MidiOptions::default()
+        };
+        let chart = convert_midi(&bytes, &options).unwrap();
+        assert_eq!(times(&chart), vec![0.5, 1.0]);
+        let slots: Vec<u8> = chart.notes.iter().map(|note| note.slot).collect();
+        assert_eq!(slots, vec![1, 0]);
+
+        let options = MidiOptions {
+            channels: vec![3],
+            ..MidiOptions::default()
+        };
+        assert!(matches!(
+            convert_midi(&bytes, &options),
+            Err(MidiError::NoNotes)
+        ));
+    }
+
+    #[test]
+    fn notes_closer_than_the_least_gap_are_left_out() {
+        // 48 ticks is 0.05 seconds at the default tempo
+        let bytes = midi_file(vec![note(0, 0, 60), note(48, 0, 62), note(432, 0, 64)]);
+        let chart = convert_midi(&bytes, &MidiOptions::default()).unwrap();
+        assert_eq!(times(&chart), vec![0.0, 0.5]);
+
+        let options = MidiOptions {
+            min_gap: 0.0,
+            ..MidiOptions::default()
+        };
+        let chart = convert_midi(&bytes, &options).unwrap();
+        assert_eq!(times(&chart), vec![0.0, 0.05, 0.5]);
+    }
+
+    #[test]
+    fn chords_are_cut_down_to_their_highest_notes() {
+        let bytes = midi_file(vec![note(0, 0, 60), note(0, 0, 64), note(0, 0, 67)]);
+        let slots = |max_chord: usize| -> Vec<u8> {
+            let options = MidiOptions {
+                max_chord,
+                ..MidiOptions::default()
+            };
+            let chart = convert_midi(&bytes, &options).unwrap();
+            chart.notes.iter().map(|note| note.slot).collect()
+        };
+        assert_eq!(slots(1), vec![3]);
+        assert_eq!(slots(2), vec![3, 2]);
+        assert_eq!(slots(3), vec![3, 2, 0]);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::BASE_SPEED;
+use cr_tile_game_common::chart::{Chart, ChartNote, MAX_SLOT_COUNT};
+use cr_tile_game_common::tile::TileKind;
+
+/// The width of the playfield in osu! pixels, columns of a mania beatmap are spread evenly across it
+const PLAYFIELD_WIDTH: f32 = 512.0;
+/// The slowest speed a timing point can slow tiles down to
+const MIN_SPEED: f32 = 1.0;
+/// The fastest speed a timing point can speed tiles up to
+const MAX_SPEED: f32 = 12.0;
+/// The bit of the type of a hit object that is set for hold notes
+const HOLD_TYPE: u32 = 128;
+
+#[derive(Debug)]
+pub enum OsuError {
+    /// The beatmap is not for osu!mania, the mode it is for is given
+    NotMania(u32),
+    /// The key count of the beatmap is outside the slot counts the game supports
+    InvalidKeyCount(f32),
+    /// The line at the given line number could not be read
+    InvalidLine(usize),
+    /// The beatmap does not have any notes
+    NoNotes,
+}
+
+/// A timing point of a beatmap, which either sets the tempo or changes the scroll speed from its time on
+#[derive(Debug, Clone, PartialEq)]
+struct TimingPoint {
+    /// The time in milliseconds the timing point starts at
+    time: f32,
+    /// The length of a beat in milliseconds for uninherited points, or the negative inverse percentage of the scroll speed
+    beat_length: f32,
+    /// True for points that set the tempo, false for points that only change the scroll speed
+    uninherited: bool,
+}
+
+/// Returns the value of a `Key: Value` line
+fn value_of<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (line_key, value) = line.split_once(':')?;
+    (line_key.trim() == key).then(|| value.trim())
+}
+
+/// Returns the scroll speed multiplier that applies at the given time
+fn speed_multiplier(timing_points: &[TimingPoint], time: f32) -> f32 {
+    timing_points
+        .iter()
+        .rev()
+        .find(|point| point.time <= time)
+        .map_or(1.0, |point| {
+            if point.uninherited || point.beat_length >= 0.0 {
+                1.0
+            } else {
+                -100.0 / point.beat_length
+            }
+        })
+}
+
+/// Converts an osu!mania beatmap (a `.osu` file) to a chart.
+/// The first tempo of the beatmap becomes the tempo of the chart, and scroll speed changes change the speed of the tiles after them.
+/// Hold notes become hold tiles that last until the end of the hold.
+pub fn convert_osu(source: &str) -> Result<Chart, OsuError> {
+    let mut section = String::new();
+    let mut mode = 0;
+    let mut key_count = None;
+    let mut audio = None;
+    let mut title = String::new();
+    let mut artist = String::new();
+    let mut creator = String::new();
+    let mut version = String::new();
+    let mut timing_points = vec![];
+    // the time in milliseconds, the column, and the end time in milliseconds of holds, of every note
+    let mut hit_objects = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].to_string();
+            continue;
+        }
+
+        match section.as_str() {
+            "General" => {
+                if let Some(value) = value_of(line, "AudioFilename") {
+                    audio = Some(value.to_string());
+                }
+                if let Some(value) = value_of(line, "Mode") {
+                    mode = value
+                        .parse()
+                        .map_err(|_| OsuError::InvalidLine(line_number))?;
+                }
+            }
+            "Metadata" => {
+                if let Some(value) = value_of(line, "Title") {
+                    title = value.to_string();
+                }
+                if let Some(value) = value_of(line, "Artist") {
+                    artist = value.to_string();
+                }
+                if let Some(value) = value_of(line, "Creator") {
+                    creator = value.to_string();
+                }
+                if let Some(value) = value_of(line, "Version") {
+                    version = value.to_string();
+                }
+            }
+            "Difficulty" => {
+                if let Some(value) = value_of(line, "CircleSize") {
+                    let keys: f32 = value
+                        .parse()
+                        .map_err(|_| OsuError::InvalidLine(line_number))?;
+                    key_count = Some(keys);
+                }
+            }
+            "TimingPoints" => {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                if fields.len() < 2 {
+                    return Err(OsuError::InvalidLine(line_number));
+                }
+                let time: f32 = fields[0]
+                    .parse()
+                    .map_err(|_| OsuError::InvalidLine(line_number))?;
+                let beat_length: f32 = fields[1]
+                    .parse()
+                    .map_err(|_| OsuError::InvalidLine(line_number))?;
+                // points from old beatmaps leave out the uninherited field, and always set the tempo
+                let uninherited = fields.get(6) != Some(&"0");
+                timing_points.push(TimingPoint {
+                    time,
+                    beat_length,
+                    uninherited,
+                });
+            }
+            "HitObjects" => {
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                if fields.len() < 3 {
+                    return Err(OsuError::InvalidLine(line_number));
+                }
+                let x: f32 = fields[0]
+                    .parse()
+                    .map_err(|_| OsuError::InvalidLine(line_number))?;
+                let time: f32 = fields[2]
+                    .parse()
+                    .map_err(|_| OsuError::InvalidLine(line_number))?;
+                let object_type: u32 = fields
+                    .get(3)
+                    .map_or(Ok(0), |field| field.parse())
+                    .map_err(|_| OsuError::InvalidLine(line_number))?;
+                // hold notes keep their end time at the start of the extra parameters
+                let end_time = match object_type & HOLD_TYPE {
+                    0 => None,
+                    _ => {
+                        let end_time: f32 = fields
+                            .get(5)
+                            .and_then(|field| field.split(':').next())
+                            .and_then(|field| field.parse().ok())
+                            .ok_or(OsuError::InvalidLine(line_number))?;
+                        Some(end_time)
+                    }
+                };
+                hit_objects.push((time, x, end_time));
+            }
+            _ => {}
+        }
+    }
+
+    if mode != 3 {
+        return Err(OsuError::NotMania(mode));
+    }
+    let key_count = key_count.unwrap_or(0.0);
+    if key_count.fract() != 0.0 || key_count < 1.0 || key_count > MAX_SLOT_COUNT as f32 {
+        return Err(OsuError::InvalidKeyCount(key_count));
+    }
+    if hit_objects.is_empty() {
+        return Err(OsuError::NoNotes);
+    }
+    let slot_count = key_count as u8;
+
+    timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+    let bpm = timing_points
+        .iter()
+        .find(|point| point.uninherited && point.beat_length > 0.0)
+        .map(|point| 60000.0 / point.beat_length);
+
+    let notes = hit_objects
+        .into_iter()
+        .map(|(time, x, end_time)| {
+            let column = (x * key_count / PLAYFIELD_WIDTH).floor() as u8;
+            // holds that end before they start are broken, and are played as a normal tile
+            let kind = match end_time {
+                Some(end_time) if end_time > time => TileKind::Hold {
+                    duration: (end_time - time) / 1000.0,
+                },
+                _ => TileKind::Normal,
+            };
+            ChartNote {
+                // notes before the song starts are moved to the start, so the chart stays valid
+                time: (time / 1000.0).max(0.0),
+                slot: column.min(slot_count - 1),
+                kind,
+                speed: (BASE_SPEED * speed_multiplier(&timing_points, time))
+                    .clamp(MIN_SPEED, MAX_SPEED),
+            }
+        })
+        .collect();
+
+    let title = match (artist.is_empty(), version.is_empty()) {
+        (true, true) => title,
+        (true, false) => format!("{} [{}]", title, version),
+        (false, true) => format!("{} - {}", artist, title),
+        (false, false) => format!("{} - {} [{}]", artist, title, version),
+    };
+
+    Ok(Chart {
+        title,
+        author: creator,
+        slot_count,
+        audio,
+        bpm,
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an osu!mania beatmap with the given key count, timing points and hit objects, each given as the lines of its section
+    fn beatmap(key_count: &str, timing_points: &str, hit_objects: &str) -> String {
+        format!(
+            "osu file format v14\n\
+             [General]\nAudioFilename: song.mp3\nMode: 3\n\
+             [Metadata]\nTitle:Song\nArtist:Artist\nCreator:Mapper\nVersion:Easy\n\
+             [Difficulty]\nCircleSize:{}\n\
+             [TimingPoints]\n{}\n\
+             [HitObjects]\n{}\n",
+            key_count, timing_points, hit_objects
+        )
+    }
+
+    #[test]
+    fn key_counts_the_game_can_not_play_are_rejected() {
+        for key_count in ["0", "7", "4.5"] {
+            let source = beatmap(key_count, "0,500,4,2,0,100,1,0", "64,192,1000,1,0,0:0:0:0:");
+            assert!(matches!(
+                convert_osu(&source),
+                Err(OsuError::InvalidKeyCount(_))
+            ));
+        }
+        let source = beatmap("4", "0,500,4,2,0,100,1,0", "64,192,1000,1,0,0:0:0:0:")
+            .replace("Mode: 3", "Mode: 0");
+        assert!(matches!(convert_osu(&source), Err(OsuError::NotMania(0))));
+    }
+
+    #[test]
+    fn hold_notes_become_hold_tiles() {
+        let source = beatmap(
+            "4",
+            "0,500,4,2,0,100,1,0",
+            "64,192,1000,128,0,1750:0:0:0:0:\n192,192,2000,128,0,1500:0:0:0:0:",
+        );
+        let chart = convert_osu(&source).unwrap();
+        assert_eq!(chart.title, "Artist - Song [Easy]");
+        assert_eq!(chart.bpm, Some(120.0));
+        assert_eq!(chart.notes[0].time, 1.0);
+        assert_eq!(chart.notes[0].kind, TileKind::Hold { duration: 0.75 });
+        // a hold that ends before it starts is played as a normal tile
+        assert_eq!(chart.notes[1].kind, TileKind::Normal);
+    }
+
+    #[test]
+    fn inherited_timing_points_change_the_speed_of_later_tiles() {
+        let source = beatmap(
+            "4",
+            "0,500,4,2,0,100,1,0\n1500,-50,4,2,0,100,0,0\n2500,-400,4,2,0,100,0,0",
+            "64,192,1000,1,0,0:0:0:0:\n64,192,2000,1,0,0:0:0:0:\n64,192,3000,1,0,0:0:0:0:",
+        );
+        let chart = convert_osu(&source).unwrap();
+        let speeds: Vec<f32> = chart.notes.iter().map(|note| note.speed).collect();
+        // -50 doubles the speed, and -400 would quarter it but is held at the slowest speed
+        assert_eq!(speeds, vec![BASE_SPEED, BASE_SPEED * 2.0, MIN_SPEED]);
+    }
+
+    #[test]
+    fn notes_on_the_right_edge_are_put_in_the_last_slot() {
+        let source = beatmap(
+            "4",
+            "0,500,4,2,0,100,1,0",
+            "0,192,1000,1,0,0:0:0:0:\n320,192,2000,1,0,0:0:0:0:\n512,192,3000,1,0,0:0:0:0:",
+        );
+        let chart = convert_osu(&source).unwrap();
+        let slots: Vec<u8> = chart.notes.iter().map(|note| note.slot).collect();
+        assert_eq!(slots, vec![0, 2, 3]);
+    }
+}
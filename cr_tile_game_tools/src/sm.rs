@@ -0,0 +1,431 @@
+use crate::BASE_SPEED;
+use cr_tile_game_common::chart::{Chart, ChartNote, MAX_SLOT_COUNT};
+use cr_tile_game_common::tile::TileKind;
+
+/// The number of beats in a measure of note data
+const BEATS_PER_MEASURE: f32 = 4.0;
+
+#[derive(Debug)]
+pub enum SmError {
+    /// The simfile does not have the tag with the given name, which is needed to convert it
+    MissingTag(&'static str),
+    /// The tag with the given name could not be read
+    InvalidTag(String),
+    /// The simfile uses a feature of StepMania that can not be played, the feature is named
+    Unsupported(String),
+    /// The note data of the chart with the given difficulty could not be read
+    InvalidNotes(String),
+    /// None of the charts of the simfile use 1 to 5 panels
+    NoSupportedCharts,
+}
+
+/// The timing of a simfile, or of a single chart that has its own timing
+#[derive(Debug, Clone, Default)]
+struct Timing {
+    /// The time in seconds of the song that beat 0 is before
+    offset: f32,
+    /// The beat each tempo starts on, and the tempo in beats per minute
+    bpms: Vec<(f32, f32)>,
+    /// The beat each stop is on, and the length of the stop in seconds
+    stops: Vec<(f32, f32)>,
+}
+
+impl Timing {
+    /// Returns the time in seconds of the song that the beat is played at
+    fn time_at(&self, beat: f32) -> f32 {
+        let mut time = 0.0;
+        for (index, (start, bpm)) in self.bpms.iter().enumerate() {
+            if *start >= beat {
+                break;
+            }
+            let end = self
+                .bpms
+                .get(index + 1)
+                .map_or(beat, |(next, _)| next.min(beat));
+            time += (end - start) * 60.0 / bpm;
+        }
+        // a stop happens after the notes on its beat
+        time + self
+            .stops
+            .iter()
+            .filter(|(stop, _)| *stop < beat)
+            .map(|(_, length)| length)
+            .sum::<f32>()
+            - self.offset
+    }
+}
+
+/// A single chart of a simfile, before its notes are read
+#[derive(Debug, Clone, Default)]
+struct StepChart {
+    /// The name of the difficulty of the chart
+    difficulty: String,
+    /// The note data of the chart
+    notes: String,
+    /// The timing of the chart, when it is different to the timing of the simfile
+    timing: Option<Timing>,
+}
+
+/// Returns every `#TAG:value;` of the simfile in order, with comments removed
+fn parse_tags(source: &str) -> Vec<(String, String)> {
+    let source: String = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let mut tags = vec![];
+    let mut rest = source.as_str();
+    while let Some(start) = rest.find('#') {
+        rest = &rest[start + 1..];
+        let Some((tag, after)) = rest.split_once(':') else {
+            break;
+        };
+        // the last tag of a file is allowed to leave out its semicolon
+        let (value, after) = after.split_once(';').unwrap_or((after, ""));
+        tags.push((tag.trim().to_uppercase(), value.trim().to_string()));
+        rest = after;
+    }
+    tags
+}
+
+/// Parses a list of `beat=value` pairs, like the ones used by the tempos and stops of a simfile
+fn parse_pairs(tag: &str, value: &str) -> Result<Vec<(f32, f32)>, SmError> {
+    let mut pairs = value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut fields = pair.split('=').map(|field| field.trim().parse::<f32>());
+            match (fields.next(), fields.next()) {
+                (Some(Ok(beat)), Some(Ok(value))) => Ok((beat, value)),
+                _ => Err(SmError::InvalidTag(tag.to_string())),
+            }
+        })
+        .collect::<Result<Vec<(f32, f32)>, SmError>>()?;
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(pairs)
+}
+
+/// Returns an error if a timing tag the game can not play is used
+fn check_supported(tag: &str, value: &str) -> Result<(), SmError> {
+    let used = match tag {
+        "WARPS" | "DELAYS" | "FAKES" => !value.trim().is_empty(),
+        // speed and scroll changes that leave the speed at 1 are written by editors even when unused
+        "SPEEDS" | "SCROLLS" => value
+            .split(',')
+            .filter_map(|segment| segment.split('=').nth(1))
+            .any(|ratio| ratio.trim().parse::<f32>() != Ok(1.0)),
+        _ => false,
+    };
+    if used {
+        return Err(SmError::Unsupported(tag.to_lowercase()));
+    }
+    Ok(())
+}
+
+/// Sets the timing tag on the timing, returns false if the tag is not a timing tag
+fn apply_timing(timing: &mut Timing, tag: &str, value: &str) -> Result<bool, SmError> {
+    match tag {
+        "OFFSET" => {
+            timing.offset = value
+                .parse()
+                .map_err(|_| SmError::InvalidTag(tag.to_string()))?;
+        }
+        "BPMS" => {
+            timing.bpms = parse_pairs(tag, value)?;
+            if timing.bpms.iter().any(|(_, bpm)| *bpm <= 0.0) {
+                return Err(SmError::Unsupported("negative or zero bpms".to_string()));
+            }
+        }
+        "STOPS" | "FREEZES" => {
+            timing.stops = parse_pairs(tag, value)?;
+            if timing.stops.iter().any(|(_, length)| *length < 0.0) {
+                return Err(SmError::Unsupported("negative stops".to_string()));
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// A note read from the note data of a chart
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    /// The beat the note is on
+    beat: f32,
+    /// The panel the note is in
+    panel: u8,
+    /// The beat a hold or roll ends on
+    end_beat: Option<f32>,
+    /// True if the note is a mine, which is played as a bomb
+    mine: bool,
+}
+
+/// Reads the note data of a chart, and returns the number of panels and every note
+fn parse_notes(difficulty: &str, notes: &str) -> Result<(usize, Vec<Step>), SmError> {
+    let invalid = || SmError::InvalidNotes(difficulty.to_string());
+    let mut panel_count = None;
+    let mut steps: Vec<Step> = vec![];
+    // the index in the steps of the hold or roll that has started in each panel, until its end is reached
+    let mut open_holds: Vec<Option<usize>> = vec![];
+
+    for (measure_index, measure) in notes.split(',').enumerate() {
+        let rows: Vec<&str> = measure
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        for (row_index, row) in rows.iter().enumerate() {
+            let width = row.chars().count();
+            if *panel_count.get_or_insert(width) != width {
+                return Err(invalid());
+            }
+            if width > MAX_SLOT_COUNT as usize {
+                // the chart is skipped, so its notes do not need to be read
+                return Ok((width, vec![]));
+            }
+            let beat =
+                (measure_index as f32 + row_index as f32 / rows.len() as f32) * BEATS_PER_MEASURE;
+            open_holds.resize(width, None);
+            for (panel, step) in row.chars().enumerate() {
+                let note = Step {
+                    beat,
+                    panel: panel as u8,
+                    end_beat: None,
+                    mine: false,
+                };
+                match step {
+                    '1' => steps.push(note),
+                    // the start of holds and rolls, rolls are played as holds
+                    '2' | '4' => {
+                        open_holds[panel] = Some(steps.len());
+                        steps.push(note);
+                    }
+                    '3' => {
+                        let start = open_holds[panel].take().ok_or_else(invalid)?;
+                        steps[start].end_beat = Some(beat);
+                    }
+                    // empty panels, fake notes that are never judged, and keysounds
+                    '0' | 'F' | 'K' => {}
+                    'M' => steps.push(Step { mine: true, ..note }),
+                    'L' => return Err(SmError::Unsupported("lifts".to_string())),
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+    }
+
+    Ok((panel_count.ok_or_else(invalid)?, steps))
+}
+
+/// Converts a StepMania simfile (a `.sm` or `.ssc` file) to a chart for every difficulty that uses 1 to 5 panels.
+/// Tempo changes and stops are worked into the times of the tiles, holds and rolls become hold tiles, and mines become bombs.
+/// Returns the name of each difficulty along with its chart.
+pub fn convert_sm(source: &str) -> Result<Vec<(String, Chart)>, SmError> {
+    let mut title = String::new();
+    let mut artist = String::new();
+    let mut credit = String::new();
+    let mut audio = None;
+    let mut timing = Timing::default();
+    let mut step_charts: Vec<StepChart> = vec![];
+    // true once a `#NOTEDATA` tag of an ssc file has started a chart, tags after it belong to that chart
+    let mut in_chart = false;
+
+    for (tag, value) in parse_tags(source) {
+        check_supported(&tag, &value)?;
+        if tag == "NOTEDATA" {
+            in_chart = true;
+            step_charts.push(StepChart::default());
+            continue;
+        }
+        if in_chart {
+            let chart = step_charts.last_mut().unwrap();
+            match tag.as_str() {
+                "DIFFICULTY" => chart.difficulty = value,
+                "NOTES" => chart.notes = value,
+                _ => {
+                    // timing tags inside a chart give it its own timing, starting from the timing of the simfile
+                    let mut chart_timing = chart.timing.clone().unwrap_or_else(|| timing.clone());
+                    if apply_timing(&mut chart_timing, &tag, &value)? {
+                        chart.timing = Some(chart_timing);
+                    }
+                }
+            }
+            continue;
+        }
+        match tag.as_str() {
+            "TITLE" => title = value,
+            "ARTIST" => artist = value,
+            "CREDIT" => credit = value,
+            "MUSIC" if !value.is_empty() => audio = Some(value),
+            // charts of sm files are a single tag, with their fields split by colons
+            "NOTES" => {
+                let fields: Vec<&str> = value.splitn(6, ':').map(str::trim).collect();
+                if fields.len() != 6 {
+                    return Err(SmError::InvalidTag(tag));
+                }
+                step_charts.push(StepChart {
+                    difficulty: fields[2].to_string(),
+                    notes: fields[5].to_string(),
+                    timing: None,
+                });
+            }
+            _ => {
+                apply_timing(&mut timing, &tag, &value)?;
+            }
+        }
+    }
+
+    if step_charts.is_empty() {
+        return Err(SmError::MissingTag("NOTES"));
+    }
+
+    let mut charts = vec![];
+    for step_chart in step_charts {
+        let timing = step_chart.timing.as_ref().unwrap_or(&timing);
+        if timing.bpms.is_empty() {
+            return Err(SmError::MissingTag("BPMS"));
+        }
+        let (panel_count, steps) = parse_notes(&step_chart.difficulty, &step_chart.notes)?;
+        if panel_count > MAX_SLOT_COUNT as usize || steps.is_empty() {
+            continue;
+        }
+
+        let notes = steps
+            .into_iter()
+            .map(|step| {
+                // notes before the song starts are moved to the start, so the chart stays valid
+                let time = timing.time_at(step.beat).max(0.0);
+                // a hold with no end is played as a tap
+                let duration = step
+                    .end_beat
+                    .map(|end_beat| timing.time_at(end_beat) - time);
+                let kind = match duration {
+                    _ if step.mine => TileKind::Bomb,
+                    Some(duration) if duration > 0.0 => TileKind::Hold { duration },
+                    _ => TileKind::Normal,
+                };
+                ChartNote {
+                    time,
+                    slot: step.panel,
+                    kind,
+                    speed: BASE_SPEED,
+                }
+            })
+            .collect();
+
+        let name = if artist.is_empty() {
+            title.clone()
+        } else {
+            format!("{} - {}", artist, title)
+        };
+        charts.push((
+            step_chart.difficulty.clone(),
+            Chart {
+                title: format!("{} [{}]", name, step_chart.difficulty),
+                author: credit.clone(),
+                slot_count: panel_count as u8,
+                audio: audio.clone(),
+                bpm: timing.bpms.first().map(|(_, bpm)| *bpm),
+                notes,
+            },
+        ));
+    }
+
+    if charts.is_empty() {
+        return Err(SmError::NoSupportedCharts);
+    }
+    Ok(charts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an sm file with the given timing tags and a single chart with the given note data
+    fn simfile(timing: &str, notes: &str) -> String {
+        format!(
+            "#TITLE:Song;\n#ARTIST:Artist;\n#MUSIC:song.ogg;\n{}\n\
+             #NOTES:\n     dance-single:\n     Mapper:\n     Easy:\n     1:\n     0,0,0,0,0:\n{}\n;\n",
+            timing, notes
+        )
+    }
+
+    #[test]
+    fn beats_follow_every_tempo_change() {
+        let timing = Timing {
+            offset: 0.1,
+            bpms: vec![(0.0, 120.0), (4.0, 240.0)],
+            stops: vec![],
+        };
+        assert_eq!(timing.time_at(0.0), -0.1);
+        assert_eq!(timing.time_at(4.0), 1.9);
+        assert_eq!(timing.time_at(8.0), 2.9);
+    }
+
+    #[test]
+    fn stops_delay_the_beats_after_them() {
+        let timing = Timing {
+            offset: 0.0,
+            bpms: vec![(0.0, 120.0)],
+            stops: vec![(4.0, 1.0)],
+        };
+        // the notes on the beat of a stop are played before it
+        assert_eq!(timing.time_at(4.0), 2.0);
+        assert_eq!(timing.time_at(5.0), 3.5);
+    }
+
+    #[test]
+    fn holds_rolls_and_mines_become_their_tiles() {
+        let source = simfile("#BPMS:0=120;", "2400\n3000\n03M1\n0000");
+        let charts = convert_sm(&source).unwrap();
+        assert_eq!(charts.len(), 1);
+        let (difficulty, chart) = &charts[0];
+        assert_eq!(difficulty, "Easy");
+        assert_eq!(chart.title, "Artist - Song [Easy]");
+        assert_eq!(chart.slot_count, 4);
+        let notes: Vec<(f32, u8, TileKind)> = chart
+            .notes
+            .iter()
+            .map(|note| (note.time, note.slot, note.kind))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (0.0, 0, TileKind::Hold { duration: 0.5 }),
+                (0.0, 1, TileKind::Hold { duration: 1.0 }),
+                (1.0, 2, TileKind::Bomb),
+                (1.0, 3, TileKind::Normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn charts_of_an_ssc_file_can_have_their_own_timing() {
+        let source = "#TITLE:Song;\n#BPMS:0=120;\n\
+             #NOTEDATA:;\n#DIFFICULTY:Easy;\n#NOTES:\n0000\n0000\n0000\n0000\n,\n1000\n0000\n0000\n0000\n;\n\
+             #NOTEDATA:;\n#DIFFICULTY:Hard;\n#BPMS:0=60;\n#NOTES:\n0000\n0000\n0000\n0000\n,\n1000\n0000\n0000\n0000\n;\n";
+        let charts = convert_sm(source).unwrap();
+        let times: Vec<(&str, f32, Option<f32>)> = charts
+            .iter()
+            .map(|(difficulty, chart)| (difficulty.as_str(), chart.notes[0].time, chart.bpm))
+            .collect();
+        assert_eq!(
+            times,
+            vec![("Easy", 2.0, Some(120.0)), ("Hard", 4.0, Some(60.0))]
+        );
+    }
+
+    #[test]
+    fn warps_are_rejected() {
+        let source = simfile("#BPMS:0=120;\n#WARPS:4=2;", "1000\n0000\n0000\n0000");
+        assert!(matches!(
+            convert_sm(&source),
+            Err(SmError::Unsupported(feature)) if feature == "warps"
+        ));
+        // an empty warps tag is written by editors even when there are no warps
+        let source = simfile("#BPMS:0=120;\n#WARPS:;", "1000\n0000\n0000\n0000");
+        assert!(convert_sm(&source).is_ok());
+    }
+}